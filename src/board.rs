@@ -1,6 +1,8 @@
 // === Board Representation ===
 // Module owner: @rea78sbq
 
+use crate::magic::{bishop_attacks, rook_attacks};
+use crate::movegen::generate_moves;
 use crate::types::*;
 
 /// Undo information for unmake_move
@@ -11,17 +13,154 @@ struct MoveUndo {
     castling_rights: u8,
     en_passant_sq: Option<Square>,
     halfmove_clock: u8,
+    fullmove_number: u16,
+    hash: u64,
 }
 
+/// Random keys for Zobrist hashing
+struct ZobristKeys {
+    pieces: [[[u64; 64]; 6]; 2], // [color][piece_type][square]
+    castling: [u64; 16],         // one per castling-rights combination
+    en_passant: [u64; 8],        // one per en passant file
+    side: u64,                   // xored in when black is to move
+}
+
+/// SplitMix64 step: deterministic pseudo-random keys at compile time
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn generate_zobrist_keys() -> ZobristKeys {
+    let mut state = 0x2545F4914F6CDD1D;
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 6]; 2],
+        castling: [0; 16],
+        en_passant: [0; 8],
+        side: 0,
+    };
+
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut sq = 0;
+            while sq < 64 {
+                keys.pieces[color][piece][sq] = splitmix64(&mut state);
+                sq += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+
+    let mut i = 0;
+    while i < 16 {
+        keys.castling[i] = splitmix64(&mut state);
+        i += 1;
+    }
+
+    let mut file = 0;
+    while file < 8 {
+        keys.en_passant[file] = splitmix64(&mut state);
+        file += 1;
+    }
+
+    keys.side = splitmix64(&mut state);
+    keys
+}
+
+static ZOBRIST: ZobristKeys = generate_zobrist_keys();
+
 #[derive(Clone)]
 pub struct Board {
     pieces: [[Bitboard; 6]; 2], // [color][piece_type]
     occupancy: [Bitboard; 2],   // per color
     side_to_move: Color,
     castling_rights: u8,
+    castling_rooks: [Square; 4], // rook start square per right, by castling_index
+    chess960: bool,
     en_passant_sq: Option<Square>,
     halfmove_clock: u8,
+    fullmove_number: u16,
+    hash: u64,
     history: Vec<MoveUndo>,
+    ply: u16, // moves made (null moves included) since the position was set up
+}
+
+const STANDARD_CASTLING_ROOKS: [Square; 4] = [H1, A1, H8, A8];
+
+/// The starting position as FEN parts, for `Board::set_fen`
+pub const START_FEN: [&str; 6] = ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", "w", "KQkq", "-", "0", "1"];
+
+/// Why a FEN string was rejected by `Board::try_from_fen`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    /// Not between 4 and 6 space-separated fields
+    FieldCount(usize),
+    /// Piece placement doesn't have exactly 8 ranks
+    RankCount(usize),
+    /// A rank (1-8) whose pieces and empty squares don't add up to 8
+    RankLength(u8),
+    /// Unknown character in the piece placement
+    InvalidPiece(char),
+    SideToMove(String),
+    Castling(String),
+    EnPassant(String),
+    HalfmoveClock(String),
+    FullmoveNumber(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::FieldCount(n) => write!(f, "expected 4 to 6 fields, found {}", n),
+            FenError::RankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::RankLength(rank) => write!(f, "rank {} does not cover exactly 8 squares", rank),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece character '{}'", c),
+            FenError::SideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::Castling(s) => write!(f, "invalid castling rights '{}'", s),
+            FenError::EnPassant(s) => write!(f, "invalid en passant square '{}'", s),
+            FenError::HalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::FullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Why `Board::try_make_move` refused a move
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    /// Nothing on the from square
+    EmptySquare(Square),
+    /// The piece on the from square belongs to the side not on move
+    NotYourPiece(Square),
+    /// Not among the legal moves in this position
+    Illegal(Move),
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MoveError::EmptySquare(sq) => write!(f, "no piece on {}", square_to_algebraic(*sq)),
+            MoveError::NotYourPiece(sq) => write!(f, "piece on {} belongs to the opponent", square_to_algebraic(*sq)),
+            MoveError::Illegal(m) => write!(f, "illegal move {}", m),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// How a finished game ended, from `Board::game_result`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
 }
 
 impl Board {
@@ -32,25 +171,105 @@ impl Board {
             occupancy: [0; 2],
             side_to_move: Color::White,
             castling_rights: WHITE_KINGSIDE | WHITE_QUEENSIDE | BLACK_KINGSIDE | BLACK_QUEENSIDE,
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            chess960: false,
             en_passant_sq: None,
             halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
             history: Vec::new(),
+            ply: 0,
         };
         board.set_startpos();
         board
     }
 
-    /// Create board from FEN parts
+    /// Parse a FEN string, rejecting malformed fields instead of guessing.
+    /// The halfmove clock and fullmove number may be omitted.
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        let mut board = Self::new();
+        board.try_set_fen(fen)?;
+        Ok(board)
+    }
+
+    /// `try_from_fen` into this board, reusing its allocations. The board is
+    /// left untouched if the FEN is rejected.
+    pub fn try_set_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+        if !(4..=6).contains(&parts.len()) {
+            return Err(FenError::FieldCount(parts.len()));
+        }
+
+        let ranks: Vec<&str> = parts[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
+        }
+        for (i, rank) in ranks.iter().enumerate() {
+            let mut squares = 0;
+            for c in rank.chars() {
+                match c {
+                    '1'..='8' => squares += c as u32 - '0' as u32,
+                    'P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'p' | 'n' | 'b' | 'r' | 'q' | 'k' => squares += 1,
+                    _ => return Err(FenError::InvalidPiece(c)),
+                }
+            }
+            if squares != 8 {
+                return Err(FenError::RankLength(8 - i as u8));
+            }
+        }
+
+        if !matches!(parts[1], "w" | "b") {
+            return Err(FenError::SideToMove(parts[1].to_string()));
+        }
+
+        let castling = parts[2];
+        let valid_castling = castling == "-"
+            || castling.chars().all(|c| matches!(c, 'K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h'));
+        if !valid_castling {
+            return Err(FenError::Castling(castling.to_string()));
+        }
+
+        // The en passant target is always on the 3rd or 6th rank
+        let ep = parts[3];
+        let valid_ep = ep == "-" || square_from_algebraic(ep).is_some_and(|sq| matches!(rank_of(sq), 2 | 5));
+        if !valid_ep {
+            return Err(FenError::EnPassant(ep.to_string()));
+        }
+
+        if let Some(&clock) = parts.get(4) {
+            clock.parse::<u8>().map_err(|_| FenError::HalfmoveClock(clock.to_string()))?;
+        }
+        if let Some(&number) = parts.get(5) {
+            if !number.parse::<u16>().is_ok_and(|n| n >= 1) {
+                return Err(FenError::FullmoveNumber(number.to_string()));
+            }
+        }
+
+        self.set_fen(&parts);
+        Ok(())
+    }
+
+    /// Create board from FEN parts. Lenient: malformed fields are skipped or
+    /// defaulted; use `try_from_fen` to reject them.
     pub fn from_fen(parts: &[&str]) -> Self {
-        let mut board = Self {
-            pieces: [[0; 6]; 2],
-            occupancy: [0; 2],
-            side_to_move: Color::White,
-            castling_rights: 0,
-            en_passant_sq: None,
-            halfmove_clock: 0,
-            history: Vec::new(),
-        };
+        let mut board = Self::new();
+        board.set_fen(parts);
+        board
+    }
+
+    /// `from_fen` into this board, reusing its allocations instead of
+    /// building a new one
+    pub fn set_fen(&mut self, parts: &[&str]) {
+        self.pieces = [[0; 6]; 2];
+        self.side_to_move = Color::White;
+        self.castling_rights = 0;
+        self.castling_rooks = STANDARD_CASTLING_ROOKS;
+        self.chess960 = false;
+        self.en_passant_sq = None;
+        self.halfmove_clock = 0;
+        self.fullmove_number = 1;
+        self.history.clear();
+        self.ply = 0;
 
         // Parse piece placement (part 0)
         if !parts.is_empty() {
@@ -58,7 +277,7 @@ impl Board {
             for c in parts[0].chars() {
                 match c {
                     '/' => sq -= 16, // Next rank
-                    '1'..='8' => sq += (c as i8 - '0' as i8),
+                    '1'..='8' => sq += c as i8 - '0' as i8,
                     _ => {
                         let (piece, color) = match c {
                             'P' => (Piece::Pawn, Color::White),
@@ -75,8 +294,8 @@ impl Board {
                             'k' => (Piece::King, Color::Black),
                             _ => continue,
                         };
-                        if sq >= 0 && sq < 64 {
-                            board.pieces[color as usize][piece as usize] |= 1u64 << sq;
+                        if (0..64).contains(&sq) {
+                            self.pieces[color as usize][piece as usize] |= 1u64 << sq;
                         }
                         sq += 1;
                     }
@@ -86,43 +305,407 @@ impl Board {
 
         // Parse side to move (part 1)
         if parts.len() > 1 {
-            board.side_to_move = if parts[1] == "b" { Color::Black } else { Color::White };
+            self.side_to_move = if parts[1] == "b" { Color::Black } else { Color::White };
         }
 
-        // Parse castling rights (part 2)
+        // Parse castling rights (part 2): KQkq, or Shredder/X-FEN rook files (HAha)
         if parts.len() > 2 {
             for c in parts[2].chars() {
-                match c {
-                    'K' => board.castling_rights |= WHITE_KINGSIDE,
-                    'Q' => board.castling_rights |= WHITE_QUEENSIDE,
-                    'k' => board.castling_rights |= BLACK_KINGSIDE,
-                    'q' => board.castling_rights |= BLACK_QUEENSIDE,
-                    _ => {}
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let back_rank: Square = if color == Color::White { 0 } else { 56 };
+                let king_bb = self.pieces[color as usize][Piece::King as usize];
+                if king_bb == 0 {
+                    continue;
+                }
+                let king_file = king_bb.trailing_zeros() as u8 % 8;
+                let rooks = self.pieces[color as usize][Piece::Rook as usize];
+                let has_rook = |file: u8| rooks & (1u64 << (back_rank + file)) != 0;
+
+                // KQkq name the outermost rook on that side of the king
+                let rook_file = match c.to_ascii_lowercase() {
+                    'k' => (king_file + 1..8).rev().find(|&f| has_rook(f)),
+                    'q' => (0..king_file).find(|&f| has_rook(f)),
+                    f @ 'a'..='h' => {
+                        self.chess960 = true;
+                        Some(f as u8 - b'a')
+                    }
+                    _ => None,
+                };
+                if let Some(file) = rook_file {
+                    let kingside = file > king_file;
+                    let right = match (color, kingside) {
+                        (Color::White, true) => WHITE_KINGSIDE,
+                        (Color::White, false) => WHITE_QUEENSIDE,
+                        (Color::Black, true) => BLACK_KINGSIDE,
+                        (Color::Black, false) => BLACK_QUEENSIDE,
+                    };
+                    self.castling_rights |= right;
+                    self.castling_rooks[castling_index(right)] = back_rank + file;
                 }
             }
         }
 
-        // Parse en passant square (part 3)
+        // Parse en passant square (part 3); only the 3rd and 6th ranks can hold one
         if parts.len() > 3 && parts[3] != "-" {
-            let bytes = parts[3].as_bytes();
-            if bytes.len() >= 2 {
-                let file = bytes[0].wrapping_sub(b'a');
-                let rank = bytes[1].wrapping_sub(b'1');
-                if file < 8 && rank < 8 {
-                    board.en_passant_sq = Some(rank * 8 + file);
-                }
-            }
+            self.en_passant_sq = square_from_algebraic(parts[3]).filter(|&sq| matches!(rank_of(sq), 2 | 5));
         }
 
         // Parse halfmove clock (part 4)
         if parts.len() > 4 {
-            board.halfmove_clock = parts[4].parse().unwrap_or(0);
+            self.halfmove_clock = parts[4].parse().unwrap_or(0);
         }
 
+        // Parse fullmove number (part 5)
+        if parts.len() > 5 {
+            self.fullmove_number = parts[5].parse().unwrap_or(1).max(1);
+        }
+
+        self.update_occupancy();
+        self.hash = self.compute_hash();
+    }
+
+    /// Play a move only if it is legal here, instead of panicking or corrupting
+    /// the board like `make_move` would on bad input
+    pub fn try_make_move(&mut self, m: Move) -> Result<(), MoveError> {
+        match self.piece_at(m.from) {
+            None => return Err(MoveError::EmptySquare(m.from)),
+            Some((_, color)) if color != self.side_to_move => return Err(MoveError::NotYourPiece(m.from)),
+            _ => {}
+        }
+        if !generate_moves(self).contains(&m) {
+            return Err(MoveError::Illegal(m));
+        }
+        self.make_move(m);
+        Ok(())
+    }
+
+    /// Every `by_color` piece attacking `sq`, sliders blocked by the current occupancy
+    pub fn attackers_to(&self, sq: Square, by_color: Color) -> Bitboard {
+        self.attackers_through(sq, by_color, self.occupancy[0] | self.occupancy[1])
+    }
+
+    /// Enemy pieces giving check to the side to move
+    pub fn checkers(&self) -> Bitboard {
+        let king_bb = self.pieces[self.side_to_move as usize][Piece::King as usize];
+        if king_bb == 0 {
+            return 0;
+        }
+        self.attackers_to(king_bb.trailing_zeros() as Square, self.side_to_move.opposite())
+    }
+
+    /// `color`'s pieces that are the only blocker between their king and an
+    /// enemy slider, so moving them off the line would expose the king
+    pub fn pinned(&self, color: Color) -> Bitboard {
+        let king_bb = self.pieces[color as usize][Piece::King as usize];
+        if king_bb == 0 {
+            return 0;
+        }
+        let king = king_bb.trailing_zeros() as Square;
+        let enemy = &self.pieces[color.opposite() as usize];
+        let queens = enemy[Piece::Queen as usize];
+        // Sliders aimed at the king on an empty board, then checked for exactly one blocker
+        let snipers = (rook_attacks(king, 0) & (enemy[Piece::Rook as usize] | queens))
+            | (bishop_attacks(king, 0) & (enemy[Piece::Bishop as usize] | queens));
+        let occupied = self.occupancy[0] | self.occupancy[1];
+
+        let mut pinned = 0;
+        for sniper in BitIter(snipers) {
+            let blockers = squares_between(king, sniper) & occupied;
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & self.occupancy[color as usize];
+            }
+        }
+        pinned
+    }
+
+    /// Whether the pseudo-legal move `m` checks the opponent, without making
+    /// it: direct and discovered checks, plus the castling rook, the pawn
+    /// removed by en passant and the promoted piece
+    pub fn gives_check(&self, m: Move) -> bool {
+        let us = self.side_to_move;
+        let king_bb = self.pieces[us.opposite() as usize][Piece::King as usize];
+        let Some((moving, _)) = self.piece_at(m.from) else {
+            return false;
+        };
+        if king_bb == 0 {
+            return false;
+        }
+        let king = king_bb.trailing_zeros() as Square;
+
+        // Our pieces and the occupancy as they stand after the move
+        let mut ours = self.pieces[us as usize];
+        let mut occupied = self.occupancy[0] | self.occupancy[1];
+        let (from_bb, to_bb) = (1u64 << m.from, 1u64 << m.to);
+        ours[moving as usize] &= !from_bb;
+        ours[m.promotion().unwrap_or(moving) as usize] |= to_bb;
+        occupied = (occupied & !from_bb) | to_bb;
+        if m.is_castle() {
+            let (rook_from, rook_to) = self.castling_rook_squares(m);
+            ours[Piece::Rook as usize] = (ours[Piece::Rook as usize] & !(1u64 << rook_from)) | (1u64 << rook_to);
+            occupied = (occupied & !(1u64 << rook_from)) | (1u64 << rook_to) | to_bb;
+        } else if m.is_en_passant() {
+            let captured_sq = if us == Color::White { m.to - 8 } else { m.to + 8 };
+            occupied &= !(1u64 << captured_sq);
+        }
+
+        let diagonal = ours[Piece::Bishop as usize] | ours[Piece::Queen as usize];
+        let straight = ours[Piece::Rook as usize] | ours[Piece::Queen as usize];
+        (pawn_attackers(king, us) & ours[Piece::Pawn as usize]) != 0
+            || (knight_attacks(king) & ours[Piece::Knight as usize]) != 0
+            || (bishop_attacks(king, occupied) & diagonal) != 0
+            || (rook_attacks(king, occupied) & straight) != 0
+    }
+
+    /// Copy the position (not the move history) of any board implementation
+    pub fn from_position(other: &impl ChessBoard) -> Self {
+        let mut board = Self {
+            pieces: [[0; 6]; 2],
+            occupancy: [0; 2],
+            side_to_move: other.side_to_move(),
+            castling_rights: other.castling_rights(),
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            chess960: false,
+            en_passant_sq: other.en_passant_square(),
+            halfmove_clock: other.halfmove_clock(),
+            fullmove_number: 1,
+            hash: 0,
+            history: Vec::new(),
+            ply: 0,
+        };
+        for color in [Color::White, Color::Black] {
+            for piece in [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+                board.pieces[color as usize][piece as usize] = other.pieces(color, piece);
+            }
+        }
+        for right in [WHITE_KINGSIDE, WHITE_QUEENSIDE, BLACK_KINGSIDE, BLACK_QUEENSIDE] {
+            board.castling_rooks[castling_index(right)] = other.castling_rook(right);
+        }
+        board.update_occupancy();
+        board.hash = board.compute_hash();
+        board
+    }
+
+    /// The same position flipped top to bottom with the colors swapped, so
+    /// white's pieces on rank 1 become black's on rank 8 and the other side
+    /// is to move. Move history isn't carried over. Scores are from the side
+    /// to move's point of view, so a symmetric evaluation gives both the same.
+    pub fn mirror(&self) -> Board {
+        let rights = self.castling_rights;
+        let rooks = self.castling_rooks;
+        let mut board = Self {
+            pieces: [self.pieces[1].map(|bb| bb.swap_bytes()), self.pieces[0].map(|bb| bb.swap_bytes())],
+            occupancy: [0; 2],
+            side_to_move: self.side_to_move.opposite(),
+            castling_rights: ((rights & (WHITE_KINGSIDE | WHITE_QUEENSIDE)) << 2) | (rights >> 2),
+            castling_rooks: [rooks[2] ^ 56, rooks[3] ^ 56, rooks[0] ^ 56, rooks[1] ^ 56],
+            chess960: self.chess960,
+            en_passant_sq: self.en_passant_sq.map(|sq| sq ^ 56),
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: 0,
+            history: Vec::new(),
+            ply: 0,
+        };
         board.update_occupancy();
+        board.hash = board.compute_hash();
         board
     }
 
+    /// The side to move is in check and has no legal move
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check(self.side_to_move) && generate_moves(self).is_empty()
+    }
+
+    /// The side to move isn't in check but has no legal move
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_in_check(self.side_to_move) && generate_moves(self).is_empty()
+    }
+
+    /// Every legal move in the position
+    pub fn legal_moves(&self) -> Vec<Move> {
+        generate_moves(self)
+    }
+
+    /// The result if the game is over, None while it goes on. Checkmate and
+    /// stalemate come first; then the fifty-move rule, insufficient material
+    /// and threefold repetition (over the moves still in the history) count
+    /// as draws.
+    pub fn game_result(&self) -> Option<GameResult> {
+        if self.is_checkmate() {
+            return Some(match self.side_to_move {
+                Color::White => GameResult::BlackWins,
+                Color::Black => GameResult::WhiteWins,
+            });
+        }
+        if self.is_stalemate()
+            || self.is_fifty_move_draw()
+            || self.is_insufficient_material()
+            || self.repetition_count() >= 2
+        {
+            return Some(GameResult::Draw);
+        }
+        None
+    }
+
+    /// Neither side can ever mate: bare kings, a single minor piece, or only
+    /// bishops that all stand on the same square color
+    pub fn is_insufficient_material(&self) -> bool {
+        const DARK_SQUARES: Bitboard = 0xAA55_AA55_AA55_AA55;
+        let all = |piece: Piece| self.pieces[0][piece as usize] | self.pieces[1][piece as usize];
+        if all(Piece::Pawn) | all(Piece::Rook) | all(Piece::Queen) != 0 {
+            return false;
+        }
+        let (knights, bishops) = (all(Piece::Knight), all(Piece::Bishop));
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
+    }
+
+    /// How many times the current position occurred earlier in the move history
+    fn repetition_count(&self) -> usize {
+        self.repetition_candidates().filter(|undo| undo.hash == self.hash).count()
+    }
+
+    /// History entries, newest first, for the earlier positions that could
+    /// equal the current one. Only positions since the last capture or pawn
+    /// move can recur, and only with the same side to move; a null move
+    /// breaks the chain.
+    fn repetition_candidates(&self) -> impl Iterator<Item = &MoveUndo> {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .take_while(|undo| undo.m != Move::default())
+            .skip(1)
+            .step_by(2)
+    }
+
+    /// Whether castling moves are written king-onto-rook and FEN castling
+    /// rights by rook file when the rook isn't on its standard corner
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    /// Fullmove number: starts at 1, incremented after each Black move
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
+    /// Moves made since the position was set up, counting null moves and
+    /// unaffected by `reset_history`
+    pub fn ply(&self) -> u16 {
+        self.ply
+    }
+
+    /// Number of moves that can still be unmade
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Forget moves from before the last capture or pawn move. Repetition
+    /// detection never looks past them, so only `unmake_move` loses reach:
+    /// it can go back at most `halfmove_clock` moves afterwards.
+    pub fn reset_history(&mut self) {
+        let keep = (self.halfmove_clock as usize).min(self.history.len());
+        self.history.drain(..self.history.len() - keep);
+    }
+
+    /// Serialize the position to a six-field FEN string
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        // Piece placement, rank 8 down to rank 1
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(rank * 8 + file) {
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            fen.push((b'0' + empty) as char);
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(piece, color));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push((b'0' + empty) as char);
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        // Side to move
+        fen.push(' ');
+        fen.push(if self.side_to_move == Color::White { 'w' } else { 'b' });
+
+        // Castling rights
+        fen.push(' ');
+        if self.castling_rights == 0 {
+            fen.push('-');
+        } else {
+            for (right, c) in [(WHITE_KINGSIDE, 'K'), (WHITE_QUEENSIDE, 'Q'), (BLACK_KINGSIDE, 'k'), (BLACK_QUEENSIDE, 'q')] {
+                if self.castling_rights & right == 0 {
+                    continue;
+                }
+                // Chess960 names the castling rook's file, Shredder-style
+                let rook = self.castling_rooks[castling_index(right)];
+                if !self.chess960 {
+                    fen.push(c);
+                } else if right & (WHITE_KINGSIDE | WHITE_QUEENSIDE) != 0 {
+                    fen.push((b'A' + file_of(rook)) as char);
+                } else {
+                    fen.push((b'a' + file_of(rook)) as char);
+                }
+            }
+        }
+
+        // En passant target square
+        fen.push(' ');
+        match self.en_passant_sq {
+            Some(ep) => fen.push_str(&square_to_algebraic(ep)),
+            None => fen.push('-'),
+        }
+
+        // Halfmove clock and fullmove number
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
+    }
+
+    /// Render the board with Unicode chess glyphs, rank and file labels, and
+    /// `·` on empty dark squares. `flipped` shows it from Black's side.
+    pub fn to_unicode(&self, flipped: bool) -> String {
+        let ranks: Vec<u8> = if flipped { (0..8).collect() } else { (0..8).rev().collect() };
+        let files: Vec<u8> = if flipped { (0..8).rev().collect() } else { (0..8).collect() };
+        let mut out = String::new();
+        for &rank in &ranks {
+            out.push((b'1' + rank) as char);
+            for &file in &files {
+                out.push(' ');
+                out.push(match self.piece_at(rank * 8 + file) {
+                    Some((piece, color)) => piece_to_glyph(piece, color),
+                    None if (rank + file) % 2 == 0 => '·',
+                    None => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out.push(' ');
+        for &file in &files {
+            out.push(' ');
+            out.push((b'a' + file) as char);
+        }
+        out.push('\n');
+        out
+    }
+
     fn set_startpos(&mut self) {
         // White pieces
         self.pieces[0][Piece::Pawn as usize] = 0x000000000000FF00;
@@ -141,6 +724,7 @@ impl Board {
         self.pieces[1][Piece::King as usize] = 0x1000000000000000;
 
         self.update_occupancy();
+        self.hash = self.compute_hash();
     }
 
     fn update_occupancy(&mut self) {
@@ -151,12 +735,133 @@ impl Board {
 
     fn find_piece_at(&self, sq: Square, color: usize) -> Option<Piece> {
         let mask = 1u64 << sq;
-        for piece in [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
-            if self.pieces[color][piece as usize] & mask != 0 {
-                return Some(piece);
+        [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+            .into_iter()
+            .find(|&piece| self.pieces[color][piece as usize] & mask != 0)
+    }
+
+    /// Flip a piece on or off a square, keeping the hash in sync
+    fn toggle_piece(&mut self, color: usize, piece: Piece, sq: Square) {
+        self.pieces[color][piece as usize] ^= 1u64 << sq;
+        self.hash ^= ZOBRIST.pieces[color][piece as usize][sq as usize];
+    }
+
+    /// The en passant square, only when a pawn of the side to move stands
+    /// ready to capture onto it (otherwise it doesn't affect the position)
+    fn capturable_en_passant(&self) -> Option<Square> {
+        let ep = self.en_passant_sq?;
+        let file = ep % 8;
+        let mut capturers = 0u64;
+        let pawn_rank_sq = if self.side_to_move == Color::White { ep - 8 } else { ep + 8 };
+        if file > 0 { capturers |= 1u64 << (pawn_rank_sq - 1); }
+        if file < 7 { capturers |= 1u64 << (pawn_rank_sq + 1); }
+        if capturers & self.pieces[self.side_to_move as usize][Piece::Pawn as usize] != 0 {
+            Some(ep)
+        } else {
+            None
+        }
+    }
+
+    /// En passant key, only included when the side to move can actually capture
+    fn en_passant_key(&self) -> u64 {
+        match self.capturable_en_passant() {
+            Some(ep) => ZOBRIST.en_passant[(ep % 8) as usize],
+            None => 0,
+        }
+    }
+
+    /// Rook start and end squares for a castling move. The king always lands
+    /// on the g- or c-file, the rook next to it on the f- or d-file.
+    fn castling_rook_squares(&self, m: Move) -> (Square, Square) {
+        let back_rank = m.from - file_of(m.from);
+        let rook_file = if file_of(m.to) == 6 { 5 } else { 3 };
+        (self.castling_rooks[castling_index(castling_right_of(m))], back_rank + rook_file)
+    }
+
+    /// Compute the Zobrist hash from scratch
+    fn compute_hash(&self) -> u64 {
+        let mut hash = 0u64;
+        for color in 0..2 {
+            for piece in 0..6 {
+                for sq in BitIter(self.pieces[color][piece]) {
+                    hash ^= ZOBRIST.pieces[color][piece][sq as usize];
+                }
             }
         }
-        None
+        hash ^= ZOBRIST.castling[self.castling_rights as usize];
+        hash ^= self.en_passant_key();
+        if self.side_to_move == Color::Black {
+            hash ^= ZOBRIST.side;
+        }
+        hash
+    }
+}
+
+fn piece_to_char(piece: Piece, color: Color) -> char {
+    let c = match piece {
+        Piece::Pawn => 'p',
+        Piece::Knight => 'n',
+        Piece::Bishop => 'b',
+        Piece::Rook => 'r',
+        Piece::Queen => 'q',
+        Piece::King => 'k',
+    };
+    if color == Color::White { c.to_ascii_uppercase() } else { c }
+}
+
+fn piece_to_glyph(piece: Piece, color: Color) -> char {
+    let glyphs = if color == Color::White { ['♙', '♘', '♗', '♖', '♕', '♔'] } else { ['♟', '♞', '♝', '♜', '♛', '♚'] };
+    glyphs[piece as usize]
+}
+
+/// Boards are equal when they hold the same position: pieces, side to move,
+/// castling rights (with their rook squares) and an en passant square that can
+/// actually be captured on. Move history and the move clocks are ignored.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        let same_rooks = [WHITE_KINGSIDE, WHITE_QUEENSIDE, BLACK_KINGSIDE, BLACK_QUEENSIDE]
+            .into_iter()
+            .filter(|&right| self.castling_rights & right != 0)
+            .all(|right| self.castling_rook(right) == other.castling_rook(right));
+        self.pieces == other.pieces
+            && self.side_to_move == other.side_to_move
+            && self.castling_rights == other.castling_rights
+            && same_rooks
+            && self.capturable_en_passant() == other.capturable_en_passant()
+    }
+}
+
+impl Eq for Board {}
+
+/// Formats as the FEN string, exactly as `to_fen`
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.to_fen())
+    }
+}
+
+/// Boards serialize as their FEN string: compact, readable, and independent of
+/// the bitboard layout. Move history isn't kept.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        Board::try_from_fen(&fen).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The Zobrist key covers exactly the state PartialEq compares (apart from the
+/// castling rook squares, which only refine it), so equal boards hash equally
+impl std::hash::Hash for Board {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
     }
 }
 
@@ -167,6 +872,34 @@ impl Default for Board {
 }
 
 // Attack generation helpers (used by is_square_attacked)
+/// Squares a `by_color` pawn would have to stand on to attack `sq`
+/// (shifts off the top or bottom of the board vanish; the file masks drop the
+/// ones that would wrap from one edge file onto the other)
+fn pawn_attackers(sq: Square, by_color: Color) -> Bitboard {
+    const NOT_FILE_A: Bitboard = !0x0101_0101_0101_0101;
+    const NOT_FILE_H: Bitboard = !0x8080_8080_8080_8080;
+    let bb = 1u64 << sq;
+    match by_color {
+        // White pawns attack diagonally upward, so they sit below the square
+        Color::White => ((bb >> 9) & NOT_FILE_H) | ((bb >> 7) & NOT_FILE_A),
+        // Black pawns attack diagonally downward
+        Color::Black => ((bb << 7) & NOT_FILE_H) | ((bb << 9) & NOT_FILE_A),
+    }
+}
+
+/// Squares strictly between two squares on a shared rank, file or diagonal
+/// (empty if they don't share one)
+pub(crate) fn squares_between(a: Square, b: Square) -> Bitboard {
+    let (a_bb, b_bb) = (1u64 << a, 1u64 << b);
+    if rook_attacks(a, 0) & b_bb != 0 {
+        rook_attacks(a, b_bb) & rook_attacks(b, a_bb)
+    } else if bishop_attacks(a, 0) & b_bb != 0 {
+        bishop_attacks(a, b_bb) & bishop_attacks(b, a_bb)
+    } else {
+        0
+    }
+}
+
 fn knight_attacks(sq: Square) -> Bitboard {
     let bb = 1u64 << sq;
     let mut attacks = 0u64;
@@ -201,33 +934,6 @@ fn king_attacks(sq: Square) -> Bitboard {
     attacks
 }
 
-fn sliding_attacks(sq: Square, blockers: Bitboard, diagonal: bool) -> Bitboard {
-    let mut attacks = 0u64;
-    let directions: &[(i8, i8)] = if diagonal {
-        &[(1, 1), (1, -1), (-1, 1), (-1, -1)]
-    } else {
-        &[(0, 1), (0, -1), (1, 0), (-1, 0)]
-    };
-
-    for &(dr, df) in directions {
-        let mut r = (sq / 8) as i8;
-        let mut f = (sq % 8) as i8;
-        loop {
-            r += dr;
-            f += df;
-            if r < 0 || r > 7 || f < 0 || f > 7 {
-                break;
-            }
-            let target = (r * 8 + f) as Square;
-            attacks |= 1u64 << target;
-            if blockers & (1u64 << target) != 0 {
-                break;
-            }
-        }
-    }
-    attacks
-}
-
 impl ChessBoard for Board {
     fn piece_at(&self, sq: Square) -> Option<(Piece, Color)> {
         let mask = 1u64 << sq;
@@ -259,6 +965,10 @@ impl ChessBoard for Board {
         self.castling_rights
     }
 
+    fn castling_rook(&self, right: u8) -> Square {
+        self.castling_rooks[castling_index(right)]
+    }
+
     fn en_passant_square(&self) -> Option<Square> {
         self.en_passant_sq
     }
@@ -266,55 +976,57 @@ impl ChessBoard for Board {
     fn make_move(&mut self, m: Move) {
         let us = self.side_to_move as usize;
         let them = self.side_to_move.opposite() as usize;
-        let from_mask = 1u64 << m.from;
-        let to_mask = 1u64 << m.to;
 
         // Find the moving piece
         let moving_piece = self.find_piece_at(m.from, us).expect("no piece at from square");
 
+        // Save undo info before anything changes
+        let undo_castling = self.castling_rights;
+        let undo_ep = self.en_passant_sq;
+        let undo_halfmove = self.halfmove_clock;
+        let undo_fullmove = self.fullmove_number;
+        let undo_hash = self.hash;
+
+        // Remove the old en passant key (re-added below if still relevant)
+        self.hash ^= self.en_passant_key();
+
         // Find captured piece (if any) - but not for en passant (handled separately)
         let mut captured = None;
-        if !m.is_en_passant {
-            for piece in [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
-                if self.pieces[them][piece as usize] & to_mask != 0 {
-                    captured = Some(piece);
-                    self.pieces[them][piece as usize] ^= to_mask;
-                    break;
-                }
+        if !m.is_en_passant() {
+            if let Some(piece) = self.find_piece_at(m.to, them) {
+                captured = Some(piece);
+                self.toggle_piece(them, piece, m.to);
             }
         }
 
-        // Save undo info
         self.history.push(MoveUndo {
             m,
             captured,
-            castling_rights: self.castling_rights,
-            en_passant_sq: self.en_passant_sq,
-            halfmove_clock: self.halfmove_clock,
+            castling_rights: undo_castling,
+            en_passant_sq: undo_ep,
+            halfmove_clock: undo_halfmove,
+            fullmove_number: undo_fullmove,
+            hash: undo_hash,
         });
+        self.ply += 1;
 
         // Clear en passant (will be set if double pawn push)
         self.en_passant_sq = None;
 
-        // Handle castling
-        if m.is_castle {
-            // Move king
-            self.pieces[us][Piece::King as usize] ^= from_mask | to_mask;
-
-            // Move rook
-            let (rook_from, rook_to) = if m.to > m.from {
-                // Kingside
-                if us == 0 { (H1, F1) } else { (H8, F8) }
-            } else {
-                // Queenside
-                if us == 0 { (A1, D1) } else { (A8, D8) }
-            };
-            self.pieces[us][Piece::Rook as usize] ^= (1u64 << rook_from) | (1u64 << rook_to);
+        // Handle castling: lift king and rook, then drop them, since in
+        // Chess960 their start and end squares may coincide or overlap
+        if m.is_castle() {
+            let (rook_from, rook_to) = self.castling_rook_squares(m);
+            self.toggle_piece(us, Piece::King, m.from);
+            self.toggle_piece(us, Piece::Rook, rook_from);
+            self.toggle_piece(us, Piece::King, m.to);
+            self.toggle_piece(us, Piece::Rook, rook_to);
         }
         // Handle en passant capture
-        else if m.is_en_passant {
+        else if m.is_en_passant() {
             // Move pawn
-            self.pieces[us][Piece::Pawn as usize] ^= from_mask | to_mask;
+            self.toggle_piece(us, Piece::Pawn, m.from);
+            self.toggle_piece(us, Piece::Pawn, m.to);
 
             // Remove captured pawn (one rank behind the destination)
             let captured_sq = if self.side_to_move == Color::White {
@@ -322,28 +1034,27 @@ impl ChessBoard for Board {
             } else {
                 m.to + 8
             };
-            self.pieces[them][Piece::Pawn as usize] ^= 1u64 << captured_sq;
+            self.toggle_piece(them, Piece::Pawn, captured_sq);
         }
         // Normal move
         else {
-            self.pieces[us][moving_piece as usize] ^= from_mask | to_mask;
+            self.toggle_piece(us, moving_piece, m.from);
 
             // Handle promotion
-            if let Some(promo) = m.promotion {
-                self.pieces[us][Piece::Pawn as usize] ^= to_mask;
-                self.pieces[us][promo as usize] ^= to_mask;
+            if let Some(promo) = m.promotion() {
+                self.toggle_piece(us, promo, m.to);
+            } else {
+                self.toggle_piece(us, moving_piece, m.to);
             }
 
             // Set en passant square for double pawn push
-            if moving_piece == Piece::Pawn {
-                let diff = (m.to as i8 - m.from as i8).abs();
-                if diff == 16 {
-                    self.en_passant_sq = Some((m.from as i8 + (m.to as i8 - m.from as i8) / 2) as Square);
-                }
+            if m.is_double_push() {
+                self.en_passant_sq = Some((m.from + m.to) / 2);
             }
         }
 
         // Update castling rights
+        self.hash ^= ZOBRIST.castling[self.castling_rights as usize];
         // King moves
         if moving_piece == Piece::King {
             if us == 0 {
@@ -353,24 +1064,33 @@ impl ChessBoard for Board {
             }
         }
         // Rook moves or is captured
-        if m.from == A1 || m.to == A1 { self.castling_rights &= !WHITE_QUEENSIDE; }
-        if m.from == H1 || m.to == H1 { self.castling_rights &= !WHITE_KINGSIDE; }
-        if m.from == A8 || m.to == A8 { self.castling_rights &= !BLACK_QUEENSIDE; }
-        if m.from == H8 || m.to == H8 { self.castling_rights &= !BLACK_KINGSIDE; }
+        for right in [WHITE_KINGSIDE, WHITE_QUEENSIDE, BLACK_KINGSIDE, BLACK_QUEENSIDE] {
+            let rook = self.castling_rooks[castling_index(right)];
+            if m.from == rook || m.to == rook {
+                self.castling_rights &= !right;
+            }
+        }
+        self.hash ^= ZOBRIST.castling[self.castling_rights as usize];
 
         self.update_occupancy();
+        if self.side_to_move == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
         self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= ZOBRIST.side;
+        self.hash ^= self.en_passant_key();
 
         // Update halfmove clock
-        if captured.is_some() || m.is_en_passant || moving_piece == Piece::Pawn {
+        if captured.is_some() || m.is_en_passant() || moving_piece == Piece::Pawn {
             self.halfmove_clock = 0;
         } else {
-            self.halfmove_clock += 1;
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
         }
     }
 
     fn unmake_move(&mut self) {
         let undo = self.history.pop().expect("no move to unmake");
+        self.ply -= 1;
         let m = undo.m;
 
         self.side_to_move = self.side_to_move.opposite();
@@ -379,21 +1099,16 @@ impl ChessBoard for Board {
         let from_mask = 1u64 << m.from;
         let to_mask = 1u64 << m.to;
 
-        // Handle castling
-        if m.is_castle {
-            // Move king back
-            self.pieces[us][Piece::King as usize] ^= from_mask | to_mask;
-
-            // Move rook back
-            let (rook_from, rook_to) = if m.to > m.from {
-                if us == 0 { (H1, F1) } else { (H8, F8) }
-            } else {
-                if us == 0 { (A1, D1) } else { (A8, D8) }
-            };
-            self.pieces[us][Piece::Rook as usize] ^= (1u64 << rook_from) | (1u64 << rook_to);
+        // Handle castling (separate XORs: start and end squares may be the same)
+        if m.is_castle() {
+            let (rook_from, rook_to) = self.castling_rook_squares(m);
+            self.pieces[us][Piece::King as usize] ^= from_mask;
+            self.pieces[us][Piece::King as usize] ^= to_mask;
+            self.pieces[us][Piece::Rook as usize] ^= 1u64 << rook_from;
+            self.pieces[us][Piece::Rook as usize] ^= 1u64 << rook_to;
         }
         // Handle en passant
-        else if m.is_en_passant {
+        else if m.is_en_passant() {
             // Move pawn back
             self.pieces[us][Piece::Pawn as usize] ^= from_mask | to_mask;
 
@@ -408,7 +1123,7 @@ impl ChessBoard for Board {
         // Normal move
         else {
             // Handle promotion
-            if let Some(promo) = m.promotion {
+            if let Some(promo) = m.promotion() {
                 self.pieces[us][promo as usize] ^= to_mask;
                 self.pieces[us][Piece::Pawn as usize] ^= from_mask;
             } else {
@@ -426,11 +1141,46 @@ impl ChessBoard for Board {
         self.castling_rights = undo.castling_rights;
         self.en_passant_sq = undo.en_passant_sq;
         self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
         self.update_occupancy();
     }
 
+    /// Pass the turn without moving a piece (for null-move pruning)
+    fn make_null_move(&mut self) {
+        self.history.push(MoveUndo {
+            m: Move::default(),
+            captured: None,
+            castling_rights: self.castling_rights,
+            en_passant_sq: self.en_passant_sq,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+        });
+        self.ply += 1;
+
+        self.hash ^= self.en_passant_key();
+        self.en_passant_sq = None;
+        self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        if self.side_to_move == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+        self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= ZOBRIST.side;
+    }
+
+    fn unmake_null_move(&mut self) {
+        let undo = self.history.pop().expect("no null move to unmake");
+        self.ply -= 1;
+        self.side_to_move = self.side_to_move.opposite();
+        self.en_passant_sq = undo.en_passant_sq;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
+    }
+
     fn is_capture(&self, m: Move) -> bool {
-        if m.is_en_passant {
+        if m.is_en_passant() {
             return true;
         }
         let them = self.side_to_move.opposite();
@@ -442,66 +1192,556 @@ impl ChessBoard for Board {
     }
 
     fn zobrist_hash(&self) -> u64 {
-        0 // Phase 2
+        self.hash
+    }
+
+    fn is_repetition(&self) -> bool {
+        self.repetition_candidates().any(|undo| undo.hash == self.hash)
+    }
+
+    fn attackers_through(&self, sq: Square, by_color: Color, occupied: Bitboard) -> Bitboard {
+        let pieces = &self.pieces[by_color as usize];
+        let diagonal = pieces[Piece::Bishop as usize] | pieces[Piece::Queen as usize];
+        let straight = pieces[Piece::Rook as usize] | pieces[Piece::Queen as usize];
+        (pawn_attackers(sq, by_color) & pieces[Piece::Pawn as usize])
+            | (knight_attacks(sq) & pieces[Piece::Knight as usize])
+            | (king_attacks(sq) & pieces[Piece::King as usize])
+            | (bishop_attacks(sq, occupied) & diagonal)
+            | (rook_attacks(sq, occupied) & straight)
     }
 
     fn is_square_attacked(&self, sq: Square, by_color: Color) -> bool {
-        let attacker = by_color as usize;
-        let all_pieces = self.occupancy[0] | self.occupancy[1];
-
-        // Pawn attacks
-        let pawn_attacks = if by_color == Color::White {
-            // White pawns attack diagonally upward
-            let file = sq % 8;
-            let mut attacks = 0u64;
-            if sq >= 9 && file > 0 { attacks |= 1u64 << (sq - 9); }
-            if sq >= 7 && file < 7 { attacks |= 1u64 << (sq - 7); }
-            attacks
-        } else {
-            // Black pawns attack diagonally downward
-            let file = sq % 8;
-            let mut attacks = 0u64;
-            if sq < 55 && file < 7 { attacks |= 1u64 << (sq + 9); }
-            if sq < 57 && file > 0 { attacks |= 1u64 << (sq + 7); }
-            attacks
+        self.attackers_to(sq, by_color) != 0
+    }
+
+    fn is_in_check(&self, color: Color) -> bool {
+        let king_bb = self.pieces[color as usize][Piece::King as usize];
+        if king_bb == 0 {
+            return false;
+        }
+        let king_sq = king_bb.trailing_zeros() as Square;
+        self.is_square_attacked(king_sq, color.opposite())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::evaluate;
+    use crate::movegen::perft;
+    use crate::notation::san_to_move;
+
+    fn fen(fen: &str) -> Board {
+        Board::try_from_fen(fen).expect("valid test FEN")
+    }
+
+    fn squares(names: &[&str]) -> Bitboard {
+        names.iter().fold(0, |bb, name| bb | 1u64 << square_from_algebraic(name).expect("test square"))
+    }
+
+    fn play(board: &mut Board, sans: &[&str]) {
+        for san in sans {
+            let m = san_to_move(board, san).expect("legal test move");
+            board.make_move(m);
+        }
+    }
+
+    #[test]
+    fn make_unmake_restores_hash() {
+        let mut board = fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let original = board.zobrist_hash();
+        assert_eq!(original, board.compute_hash());
+        for m in generate_moves(&board) {
+            board.make_move(m);
+            assert_eq!(board.zobrist_hash(), board.compute_hash(), "incremental hash after {}", m);
+            board.unmake_move();
+            assert_eq!(board.zobrist_hash(), original, "hash after unmaking {}", m);
+        }
+    }
+
+    #[test]
+    fn transpositions_hash_equal() {
+        let mut a = Board::new();
+        let mut b = Board::new();
+        play(&mut a, &["Nf3", "Nf6", "d4", "d5"]);
+        play(&mut b, &["d4", "d5", "Nf3", "Nf6"]);
+        assert_eq!(a.zobrist_hash(), b.zobrist_hash());
+
+        let mut c = Board::new();
+        play(&mut c, &["d4", "Nf6", "Nf3", "d5"]);
+        assert_eq!(a.zobrist_hash(), c.zobrist_hash());
+        play(&mut c, &["e3"]);
+        assert_ne!(a.zobrist_hash(), c.zobrist_hash());
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let start = Board::new().to_fen();
+        assert_eq!(start, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let parts: Vec<&str> = start.split_whitespace().collect();
+        assert_eq!(Board::from_fen(&parts).to_fen(), start);
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 0 3",
+            "4k3/8/8/8/8/8/8/4K3 b - - 57 112",
+        ] {
+            assert_eq!(Board::try_from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fullmove_number_counts_black_moves() {
+        let mut board = Board::new();
+        assert_eq!(board.fullmove_number(), 1);
+        play(&mut board, &["e4", "e5"]);
+        assert_eq!(board.fullmove_number(), 2);
+        assert_eq!(board.side_to_move(), Color::White);
+        play(&mut board, &["Nf3"]);
+        assert_eq!(board.fullmove_number(), 2);
+        board.unmake_move();
+        board.unmake_move();
+        assert_eq!(board.fullmove_number(), 1);
+        assert_eq!(fen("4k3/8/8/8/8/8/8/4K3 b - - 0 40").fullmove_number(), 40);
+
+        // The largest move number a FEN can give stays put rather than overflowing
+        let mut board = fen("4k3/8/8/8/8/8/8/4K3 b - - 0 65535");
+        play(&mut board, &["Kd7"]);
+        assert_eq!(board.fullmove_number(), u16::MAX);
+        board.unmake_move();
+        assert_eq!(board.fullmove_number(), u16::MAX);
+    }
+
+    #[test]
+    fn lenient_fen_drops_impossible_en_passant() {
+        for ep in ["a1", "h8", "e4"] {
+            let board = Board::from_fen(&["8/8/8/8/8/8/8/8", "w", "-", ep]);
+            assert_eq!(board.en_passant_square(), None);
+        }
+        let board = Board::from_fen(&["4k3/8/8/3pP3/8/8/8/4K3", "w", "-", "d6"]);
+        assert_eq!(board.en_passant_square(), Some(square_from_algebraic("d6").unwrap()));
+    }
+
+    #[test]
+    fn chess960_perft() {
+        for (position, counts) in [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002]),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471]),
+        ] {
+            let mut board = fen(position);
+            assert!(board.is_chess960());
+            for (depth, &expected) in (1..).zip(counts.iter()) {
+                assert_eq!(perft(&mut board, depth), expected, "{} depth {}", position, depth);
+            }
+            assert_eq!(board.to_fen(), position);
+        }
+    }
+
+    #[test]
+    fn boards_compare_by_position() {
+        let mut a = Board::new();
+        let mut b = Board::new();
+        play(&mut a, &["Nf3", "Nf6", "d4", "d5"]);
+        play(&mut b, &["d4", "d5", "Nf3", "Nf6"]);
+        assert!(a == b);
+
+        // Knights out and back: same position, different clocks and history
+        let mut shuffled = Board::new();
+        play(&mut shuffled, &["Nf3", "Nf6", "Ng1", "Ng8"]);
+        assert!(shuffled == Board::new());
+
+        // An en passant square nobody can capture on doesn't count
+        let mut pushed = Board::new();
+        play(&mut pushed, &["e4"]);
+        assert!(pushed == fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"));
+        let capturable = fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        assert!(capturable != fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3"));
+
+        assert!(a != Board::new());
+        assert!(fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1") != fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+        assert!(fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1") != fen("r3k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+    }
+
+    #[test]
+    fn transpositions_share_a_hash_set_entry() {
+        let mut seen = std::collections::HashSet::new();
+        for line in [
+            &["Nf3", "Nf6", "d4", "d5"][..],
+            &["d4", "d5", "Nf3", "Nf6"],
+            &["d4", "Nf6", "Nf3", "d5"],
+            &["e4", "e5"],
+            &["e4", "e5", "Nf3", "Nc6", "Ng1", "Nb8"],
+            &["e4"],
+        ] {
+            let mut board = Board::new();
+            play(&mut board, line);
+            seen.insert(board);
+        }
+        // One position from the first three lines, one from the next two, and 1. e4
+        assert_eq!(seen.len(), 3);
+        // 1. e4 leaves an uncapturable en passant square, which doesn't split the entry
+        assert!(seen.contains(&fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")));
+        assert!(!seen.contains(&Board::new()));
+    }
+
+    #[test]
+    fn malformed_fens_name_the_problem() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+        for (text, expected) in [
+            (format!("{} w KQkq", start), FenError::FieldCount(3)),
+            (format!("{} w KQkq - 0 1 extra", start), FenError::FieldCount(7)),
+            ("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(), FenError::RankCount(7)),
+            ("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(), FenError::RankLength(7)),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1".to_string(), FenError::RankLength(1)),
+            ("rnbqkbnr/pppppppp/8/8/4X3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(), FenError::InvalidPiece('X')),
+            (format!("{} white KQkq - 0 1", start), FenError::SideToMove("white".to_string())),
+            (format!("{} w KQxq - 0 1", start), FenError::Castling("KQxq".to_string())),
+            (format!("{} w KQkq e4 0 1", start), FenError::EnPassant("e4".to_string())),
+            (format!("{} w KQkq z9 0 1", start), FenError::EnPassant("z9".to_string())),
+            (format!("{} w KQkq - -1 1", start), FenError::HalfmoveClock("-1".to_string())),
+            (format!("{} w KQkq - 0 0", start), FenError::FullmoveNumber("0".to_string())),
+            (format!("{} w KQkq - 0 one", start), FenError::FullmoveNumber("one".to_string())),
+        ] {
+            assert_eq!(Board::try_from_fen(&text).err(), Some(expected), "{}", text);
+        }
+        // Clocks are optional
+        assert!(Board::try_from_fen(&format!("{} w KQkq -", start)).is_ok());
+    }
+
+    #[test]
+    fn try_make_move_rejects_bad_moves() {
+        // The e2 knight is pinned to the king by the e8 rook
+        let position = "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1";
+        let mut board = fen(position);
+        let sq = |name| square_from_algebraic(name).unwrap();
+
+        assert_eq!(board.try_make_move(Move::new(sq("d4"), sq("d5"))), Err(MoveError::EmptySquare(sq("d4"))));
+        assert_eq!(board.try_make_move(Move::new(sq("e8"), sq("e7"))), Err(MoveError::NotYourPiece(sq("e8"))));
+        let pinned = Move::new(sq("e2"), sq("c3"));
+        assert_eq!(board.try_make_move(pinned), Err(MoveError::Illegal(pinned)));
+        assert_eq!(board.to_fen(), position);
+
+        assert_eq!(board.try_make_move(Move::new(sq("e1"), sq("d1"))), Ok(()));
+        assert_eq!(board.side_to_move(), Color::Black);
+    }
+
+    #[test]
+    fn null_move_unmakes_exactly() {
+        // With an en passant square set, which the null move has to clear and restore
+        let position = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        let mut board = fen(position);
+        let hash = board.zobrist_hash();
+
+        board.make_null_move();
+        assert_eq!(board.side_to_move(), Color::Black);
+        assert_eq!(board.en_passant_square(), None);
+        assert_eq!(board.halfmove_clock(), 1);
+        assert_eq!(board.zobrist_hash(), board.compute_hash());
+        assert_ne!(board.zobrist_hash(), hash);
+
+        board.unmake_null_move();
+        assert_eq!(board.to_fen(), position);
+        assert_eq!(board.zobrist_hash(), hash);
+        assert!(board == fen(position));
+
+        // Passing for Black at the last move number doesn't overflow it
+        let mut board = fen("4k3/8/8/8/8/8/8/4K3 b - - 0 65535");
+        board.make_null_move();
+        assert_eq!(board.fullmove_number(), u16::MAX);
+        board.unmake_null_move();
+        assert_eq!(board.fullmove_number(), u16::MAX);
+    }
+
+    #[test]
+    fn attackers_to_a_crowded_square() {
+        let board = fen("k2q4/8/1N3n2/3p3Q/2P1PN2/1B6/8/3RK3 w - - 0 1");
+        let d5 = square_from_algebraic("d5").unwrap();
+        // The b3 bishop is blocked by its own c4 pawn
+        assert_eq!(board.attackers_to(d5, Color::White), squares(&["c4", "e4", "f4", "b6", "d1", "h5"]));
+        assert_eq!(board.attackers_to(d5, Color::Black), squares(&["d8", "f6"]));
+        assert_eq!(board.attackers_to(square_from_algebraic("a4").unwrap(), Color::Black), 0);
+    }
+
+    #[test]
+    fn mirrored_positions_evaluate_the_same() {
+        // The side to move changes too, so the score must not change sign
+        let starts = [
+            START_FEN.join(" "),
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8".to_string(),
+        ];
+        let mut seed = 0x2545_F491_4F6C_DD1Du64;
+        let mut checked = 0;
+        for start in &starts {
+            let mut board = fen(start);
+            for _ in 0..60 {
+                let mirrored = board.mirror();
+                assert!(mirrored.mirror() == board);
+                assert_eq!(evaluate(&board), evaluate(&mirrored), "{}", board.to_fen());
+                checked += 1;
+
+                let moves = generate_moves(&board);
+                if moves.is_empty() {
+                    break;
+                }
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                board.make_move(moves[(seed % moves.len() as u64) as usize]);
+            }
+        }
+        assert!(checked > 100);
+    }
+
+    #[test]
+    fn piece_counts_and_material() {
+        let board = Board::new();
+        for color in [Color::White, Color::Black] {
+            let counts = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+                .map(|p| board.piece_count(color, p));
+            assert_eq!(counts, [8, 2, 2, 2, 1, 1]);
+            assert_eq!(board.non_pawn_material(color), 2 * 320 + 2 * 330 + 2 * 500 + 900);
+            assert_eq!(board.material(color), 8 * 100 + 3200);
+        }
+
+        // White: rook and two pawns; black: knight, bishop and a pawn
+        let board = fen("4k3/5p2/2n5/8/3b4/8/PP6/R3K3 w - - 0 1");
+        assert_eq!(board.piece_count(Color::White, Piece::Pawn), 2);
+        assert_eq!(board.piece_count(Color::White, Piece::Knight), 0);
+        assert_eq!(board.piece_count(Color::Black, Piece::Bishop), 1);
+        assert_eq!(board.material(Color::White), 700);
+        assert_eq!(board.non_pawn_material(Color::White), 500);
+        assert_eq!(board.material(Color::Black), 750);
+        assert_eq!(board.non_pawn_material(Color::Black), 650);
+    }
+
+    #[test]
+    fn pinned_pieces_and_checkers() {
+        // The e4 knight and d2 bishop are pinned; the g3 and f2 pawns both
+        // stand between the h4 queen and the king, so neither is
+        let board = fen("4r1k1/8/8/b7/4N2q/6P1/3B1P2/4K3 w - - 0 1");
+        assert_eq!(board.pinned(Color::White), squares(&["e4", "d2"]));
+        assert_eq!(board.checkers(), 0);
+        assert!(generate_moves(&board).iter().all(|m| m.from != square_from_algebraic("e4").expect("test square")));
+
+        // Double check: the knight on f6 and the bishop on b5 discovered by it
+        let board = fen("4k3/8/5N2/1B6/8/8/8/4K3 b - - 0 1");
+        assert_eq!(board.checkers(), squares(&["f6", "b5"]));
+        assert_eq!(board.pinned(Color::Black), 0);
+        assert!(generate_moves(&board).iter().all(|m| m.from == square_from_algebraic("e8").expect("test square")));
+    }
+
+    #[test]
+    fn gives_check_before_the_move() {
+        let checks = |f: &str, san: &str| {
+            let board = fen(f);
+            board.gives_check(san_to_move(&board, san).expect("legal test move"))
         };
-        if pawn_attacks & self.pieces[attacker][Piece::Pawn as usize] != 0 {
-            return true;
+        // Direct knight check, and a knight move that doesn't check
+        assert!(checks("4k3/8/8/3N4/8/8/8/4K3 w - - 0 1", "Nc7"));
+        assert!(!checks("4k3/8/8/3N4/8/8/8/4K3 w - - 0 1", "Nb4"));
+        // Discovered check: the bishop leaves the e-file rook's line
+        assert!(checks("4k3/8/8/8/4B3/8/8/4RK2 w - - 0 1", "Bd5"));
+        // Promotion to a queen checks along the rank, to a knight it doesn't
+        assert!(checks("7k/1P6/8/8/8/8/8/4K3 w - - 0 1", "b8=Q"));
+        assert!(!checks("7k/1P6/8/8/8/8/8/4K3 w - - 0 1", "b8=N"));
+        // The castling rook checks; en passant uncovers the a4 bishop
+        assert!(checks("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "O-O"));
+        assert!(checks("4k3/8/8/1pP5/B7/8/8/4K3 w - b6 0 1", "cxb6"));
+
+        // Agrees with making the move in every position along a game
+        let mut board = fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        for i in 0..10 {
+            let moves = generate_moves(&board);
+            for &m in moves.iter() {
+                let mut after = board.clone();
+                after.make_move(m);
+                let them = after.side_to_move();
+                assert_eq!(board.gives_check(m), after.is_in_check(them), "{} in {}", m, board.to_fen());
+            }
+            board.make_move(moves[i * 7 % moves.len()]);
         }
+    }
 
-        // Knight attacks
-        if knight_attacks(sq) & self.pieces[attacker][Piece::Knight as usize] != 0 {
-            return true;
+    #[test]
+    fn set_fen_matches_from_fen() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ];
+        // A board with history and castling already changed by play
+        let mut board = Board::new();
+        play(&mut board, &["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O"]);
+        let capacity = board.history.capacity();
+        for f in fens {
+            let parts: Vec<&str> = f.split_whitespace().collect();
+            board.set_fen(&parts);
+            let fresh = Board::from_fen(&parts);
+            assert!(board == fresh, "{}", f);
+            assert_eq!(board.to_fen(), f);
+            assert_eq!(board.zobrist_hash(), fresh.zobrist_hash());
+            assert!(board.history.is_empty());
+            assert_eq!(board.history.capacity(), capacity);
         }
+    }
 
-        // King attacks
-        if king_attacks(sq) & self.pieces[attacker][Piece::King as usize] != 0 {
-            return true;
+    #[test]
+    fn every_move_kind_makes_and_unmakes() {
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", MoveKind::Normal, "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4", MoveKind::DoublePush, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", MoveKind::Castle, "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1"),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", MoveKind::EnPassant, "4k3/8/3P4/8/8/8/8/4K3 b - - 0 1"),
+            ("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8n", MoveKind::Promotion(Piece::Knight), "N3k3/8/8/8/8/8/8/4K3 b - - 0 1"),
+            ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", MoveKind::CapturePromotion(Piece::Queen), "1Q2k3/8/8/8/8/8/8/4K3 b - - 0 1"),
+        ];
+        for (before, text, kind, after) in cases {
+            let mut board = fen(before);
+            let original = board.clone();
+            let m = generate_moves(&board).into_iter().find(|m| m.to_string() == text).expect("legal test move");
+            assert_eq!(m.kind, kind, "{}", text);
+            board.make_move(m);
+            assert_eq!(board.to_fen(), after, "after {}", text);
+            assert_eq!(board.zobrist_hash(), board.compute_hash(), "hash after {}", text);
+            board.unmake_move();
+            assert_eq!(board.to_fen(), before, "after unmaking {}", text);
+            assert!(board == original, "position after unmaking {}", text);
+            assert_eq!(board.zobrist_hash(), original.zobrist_hash(), "hash after unmaking {}", text);
         }
+    }
 
-        // Bishop/Queen (diagonal)
-        let diagonal_attackers = self.pieces[attacker][Piece::Bishop as usize]
-                                | self.pieces[attacker][Piece::Queen as usize];
-        if sliding_attacks(sq, all_pieces, true) & diagonal_attackers != 0 {
-            return true;
+    #[test]
+    fn pawn_attacks_never_wrap_around_the_board() {
+        for sq in 0..64 {
+            for color in [Color::White, Color::Black] {
+                // The pawn sits one rank behind the square, from its own side, on a neighbouring file
+                let pawn_rank = if color == Color::White { rank_of(sq) as i8 - 1 } else { rank_of(sq) as i8 + 1 };
+                let mut expected = 0;
+                for pawn_file in [file_of(sq) as i8 - 1, file_of(sq) as i8 + 1] {
+                    if (0..8).contains(&pawn_file) && (0..8).contains(&pawn_rank) {
+                        expected |= 1u64 << (pawn_rank * 8 + pawn_file);
+                    }
+                }
+                assert_eq!(pawn_attackers(sq, color), expected, "{:?} pawns attacking {}", color, square_to_algebraic(sq));
+            }
         }
 
-        // Rook/Queen (straight)
-        let straight_attackers = self.pieces[attacker][Piece::Rook as usize]
-                                | self.pieces[attacker][Piece::Queen as usize];
-        if sliding_attacks(sq, all_pieces, false) & straight_attackers != 0 {
-            return true;
+        // Edge-file pawns attack only inward
+        let board = fen("4k3/p6p/8/8/8/8/P6P/4K3 w - - 0 1");
+        let attacked = |color| (16..48).filter(|&sq| board.is_square_attacked(sq, color)).map(square_to_algebraic).collect::<Vec<_>>();
+        assert_eq!(attacked(Color::White), ["b3", "g3"]);
+        assert_eq!(attacked(Color::Black), ["b6", "g6"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips_through_the_fen() {
+        let mut board = Board::new();
+        play(&mut board, &["e4", "c5", "e5", "d5"]);
+        let json = serde_json::to_string(&board).expect("serializable board");
+        assert_eq!(json, format!("\"{}\"", board.to_fen()));
+        let back: Board = serde_json::from_str(&json).expect("deserializable board");
+        assert!(back == board);
+        assert_eq!(back.to_fen(), board.to_fen());
+        assert!(serde_json::from_str::<Board>("\"not a fen\"").is_err());
+
+        let m = Move::with_kind(52, 61, MoveKind::CapturePromotion(Piece::Knight));
+        let back: Move = serde_json::from_str(&serde_json::to_string(&m).expect("serializable move")).expect("deserializable move");
+        assert_eq!(back, m);
+        for (piece, color) in [(Piece::Queen, Color::White), (Piece::Pawn, Color::Black)] {
+            let json = serde_json::to_string(&(piece, color)).expect("serializable piece");
+            assert_eq!(serde_json::from_str::<(Piece, Color)>(&json).expect("deserializable piece"), (piece, color));
         }
+    }
 
-        false
+    #[test]
+    fn unicode_rendering_of_the_start_position() {
+        let board = Board::new();
+        for flipped in [false, true] {
+            let text = board.to_unicode(flipped);
+            let count = |glyph| text.chars().filter(|&c| c == glyph).count();
+            for (glyph, expected) in [('♔', 1), ('♕', 1), ('♖', 2), ('♗', 2), ('♘', 2), ('♙', 8), ('♚', 1), ('♛', 1), ('♜', 2), ('♝', 2), ('♞', 2), ('♟', 8)] {
+                assert_eq!(count(glyph), expected, "{} (flipped: {})", glyph, flipped);
+            }
+            // Half of the 32 empty squares are dark
+            assert_eq!(count('·'), 16);
+            assert_eq!(text.lines().count(), 9);
+        }
+        let first_rank = |flipped| board.to_unicode(flipped).lines().next().map(str::to_string).expect("a rank");
+        assert_eq!(first_rank(false), "8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜");
+        assert_eq!(first_rank(true), "1 ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖");
     }
 
-    fn is_in_check(&self, color: Color) -> bool {
-        let king_bb = self.pieces[color as usize][Piece::King as usize];
-        if king_bb == 0 {
-            return false;
+    #[test]
+    fn display_prints_the_fen() {
+        assert_eq!(format!("{}", Board::new()), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let mut board = Board::new();
+        play(&mut board, &["Nf3", "d5", "g3"]);
+        assert_eq!(board.to_string(), board.to_fen());
+    }
+
+    #[test]
+    fn back_rank_mate_and_stalemate() {
+        let mut board = fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert!(!board.is_checkmate() && !board.is_stalemate());
+        play(&mut board, &["Ra8"]);
+        assert!(board.is_checkmate());
+        assert!(!board.is_stalemate());
+
+        let board = fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert!(board.is_stalemate());
+        assert!(!board.is_checkmate());
+    }
+
+    #[test]
+    fn game_result_for_each_ending() {
+        let mut board = fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(board.game_result(), None);
+        play(&mut board, &["Ra8"]);
+        assert_eq!(board.game_result(), Some(GameResult::WhiteWins));
+
+        let mut board = Board::new();
+        play(&mut board, &["f3", "e5", "g4", "Qh4"]);
+        assert_eq!(board.game_result(), Some(GameResult::BlackWins));
+
+        // Stalemate, the fifty-move rule, and king and bishop against king
+        for position in ["7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", "4k3/8/8/8/8/8/8/R3K3 w - - 100 80", "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"] {
+            assert_eq!(fen(position).game_result(), Some(GameResult::Draw), "{}", position);
         }
-        let king_sq = king_bb.trailing_zeros() as Square;
-        self.is_square_attacked(king_sq, color.opposite())
+
+        // The start position comes back twice more
+        let mut board = Board::new();
+        play(&mut board, &["Nf3", "Nf6", "Ng1", "Ng8"]);
+        assert!(board.is_repetition());
+        assert_eq!(board.repetition_count(), 1);
+        assert_eq!(board.game_result(), None);
+        play(&mut board, &["Nf3", "Nf6", "Ng1", "Ng8"]);
+        assert_eq!(board.repetition_count(), 2);
+        assert_eq!(board.game_result(), Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn ply_follows_make_and_unmake() {
+        let mut board = Board::new();
+        assert_eq!(board.ply(), 0);
+        assert_eq!(fen("4k3/8/8/8/8/8/8/4K3 b - - 12 40").ply(), 0);
+        play(&mut board, &["e4", "e5", "Nf3"]);
+        assert_eq!(board.ply(), 3);
+        board.make_null_move();
+        assert_eq!(board.ply(), 4);
+        board.unmake_null_move();
+        assert_eq!(board.ply(), 3);
+        for expected in [2, 1, 0] {
+            board.unmake_move();
+            assert_eq!(board.ply(), expected);
+        }
+        // Dropping history that can't matter for repetition leaves the count alone
+        play(&mut board, &["d4", "d5", "Nf3"]);
+        board.reset_history();
+        assert_eq!(board.ply(), 3);
     }
 }
@@ -0,0 +1,150 @@
+// === Opening Book ===
+// Module owner: @i3mjagsb
+
+use crate::board::Board;
+use crate::movegen::generate_moves;
+use crate::types::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Opening book in a plain text format, one position per line:
+///
+/// ```text
+/// # comment
+/// rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 -> e2e4:40 d2d4:35 g1f3
+/// ```
+///
+/// Moves are in UCI notation with an optional weight (default 1). Lines that
+/// don't parse are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct Book {
+    entries: HashMap<u64, Vec<(String, u32)>>, // keyed by Zobrist hash
+}
+
+impl Book {
+    /// Read a book file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// Build a book from text in the format above
+    pub fn parse(text: &str) -> Self {
+        let mut book = Self::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((fen, moves)) = line.split_once("->") else {
+                continue;
+            };
+            let Ok(board) = Board::try_from_fen(fen.trim()) else {
+                continue;
+            };
+            let entry = book.entries.entry(board.zobrist_hash()).or_default();
+            for token in moves.split_whitespace() {
+                let (uci, weight) = match token.split_once(':') {
+                    Some((uci, weight)) => (uci, weight.parse().unwrap_or(0)),
+                    None => (token, 1),
+                };
+                if weight > 0 {
+                    entry.push((uci.to_string(), weight));
+                }
+            }
+        }
+        book
+    }
+
+    /// Number of positions in the book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Legal book moves for the position with their weights
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u32)> {
+        let Some(entry) = self.entries.get(&board.zobrist_hash()) else {
+            return Vec::new();
+        };
+        let legal = generate_moves(board);
+        entry
+            .iter()
+            .filter_map(|(uci, weight)| {
+                let m = legal.iter().find(|m| m.to_string() == *uci)?;
+                Some((*m, *weight))
+            })
+            .collect()
+    }
+
+    /// A legal book move picked at random in proportion to its weight
+    pub fn book_move(&self, board: &Board) -> Option<Move> {
+        pick_weighted(&self.moves(board))
+    }
+}
+
+/// One of `moves` at random, in proportion to its weight
+pub(crate) fn pick_weighted(moves: &[(Move, u32)]) -> Option<Move> {
+    let total: u64 = moves.iter().map(|&(_, weight)| weight as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut pick = random_seed() % total;
+    for &(m, weight) in moves {
+        if pick < weight as u64 {
+            return Some(m);
+        }
+        pick -= weight as u64;
+    }
+    None
+}
+
+/// Cheap per-call randomness from the clock, mixed so consecutive calls differ
+fn random_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    let mut z = nanos.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOK: &str = "\
+# start position
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 -> e2e4:40 d2d4:35 e2e5:10 a2a3:0
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 -> c7c5
+not a position -> e2e4
+";
+
+    fn play(board: &mut Board, uci: &str) {
+        let m = generate_moves(board).into_iter().find(|m| m.to_string() == uci).expect("legal test move");
+        board.make_move(m);
+    }
+
+    #[test]
+    fn book_position_returns_a_book_move() {
+        let book = Book::parse(BOOK);
+        assert_eq!(book.len(), 2);
+
+        let board = Board::new();
+        let names: Vec<String> = book.moves(&board).iter().map(|(m, w)| format!("{}:{}", m, w)).collect();
+        assert_eq!(names, ["e2e4:40", "d2d4:35"]);
+        for _ in 0..20 {
+            let m = book.book_move(&board).expect("start position is in the book");
+            assert!(["e2e4", "d2d4"].contains(&m.to_string().as_str()));
+        }
+
+        let mut board = Board::new();
+        play(&mut board, "e2e4");
+        assert_eq!(book.book_move(&board).map(|m| m.to_string()), Some("c7c5".to_string()));
+        play(&mut board, "c7c5");
+        assert!(book.book_move(&board).is_none());
+    }
+}
@@ -0,0 +1,172 @@
+// === EPD Parsing ===
+// Module owner: @rpbr2qqf
+
+use crate::board::{Board, FenError};
+use crate::notation::san_to_move;
+use crate::types::*;
+use std::collections::HashMap;
+
+/// One EPD line: the position and its operations
+#[derive(Clone)]
+pub struct EpdRecord {
+    pub board: Board,
+    /// Every operation by opcode, with its operands (string quotes removed)
+    pub operations: HashMap<String, Vec<String>>,
+    /// The `bm` moves, resolved against the position
+    pub best_moves: Vec<Move>,
+    /// The `am` moves, resolved against the position
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+}
+
+/// Why an EPD line couldn't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdError {
+    /// Fewer than the four position fields
+    MissingFields,
+    /// The position fields (with any hmvc/fmvn operations) aren't a valid FEN
+    Fen(FenError),
+    /// A quoted operand with no closing quote
+    UnterminatedString,
+    /// A `bm` or `am` operand that isn't exactly one legal move
+    IllegalMove { opcode: String, san: String },
+}
+
+impl std::fmt::Display for EpdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EpdError::MissingFields => write!(f, "expected 4 position fields"),
+            EpdError::Fen(e) => write!(f, "invalid position: {}", e),
+            EpdError::UnterminatedString => write!(f, "unterminated string operand"),
+            EpdError::IllegalMove { opcode, san } => write!(f, "illegal or ambiguous move '{}' in {}", san, opcode),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+/// Parse one EPD line, e.g. `... w - - bm Qg6; id "WAC.001";`. The halfmove
+/// clock and move number come from `hmvc` and `fmvn` when present.
+pub fn parse_epd(line: &str) -> Result<EpdRecord, EpdError> {
+    let mut rest = line.trim();
+    let mut fields = Vec::with_capacity(4);
+    for _ in 0..4 {
+        let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if field.is_empty() {
+            return Err(EpdError::MissingFields);
+        }
+        fields.push(field);
+        rest = tail.trim_start();
+    }
+
+    let mut operations = HashMap::new();
+    for (opcode, operands) in parse_operations(rest)? {
+        operations.insert(opcode, operands);
+    }
+    let operand = |opcode: &str, default: &'static str| {
+        operations.get(opcode).and_then(|v| v.first()).map_or(default, |s| s.as_str())
+    };
+    let fen = format!("{} {} {}", fields.join(" "), operand("hmvc", "0"), operand("fmvn", "1"));
+    let board = Board::try_from_fen(&fen).map_err(EpdError::Fen)?;
+
+    let moves = |opcode: &str| -> Result<Vec<Move>, EpdError> {
+        operations
+            .get(opcode)
+            .into_iter()
+            .flatten()
+            .map(|san| {
+                san_to_move(&board, san).ok_or_else(|| EpdError::IllegalMove { opcode: opcode.to_string(), san: san.clone() })
+            })
+            .collect()
+    };
+    let best_moves = moves("bm")?;
+    let avoid_moves = moves("am")?;
+    let id = operations.get("id").and_then(|v| v.first()).cloned();
+
+    Ok(EpdRecord { board, operations, best_moves, avoid_moves, id })
+}
+
+/// Split `op operand operand; op ...;` into opcodes and operands. Quoted
+/// operands may hold spaces and semicolons; the last `;` is optional.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if (c.is_whitespace() || c == ';' || c == '"') && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        match c {
+            '"' => {
+                let mut closed = false;
+                let quoted: String = chars
+                    .by_ref()
+                    .take_while(|&c| {
+                        closed = c == '"';
+                        !closed
+                    })
+                    .collect();
+                if !closed {
+                    return Err(EpdError::UnterminatedString);
+                }
+                words.push(quoted);
+            }
+            ';' => {
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            _ if c.is_whitespace() => {}
+            _ => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_move_and_id_are_parsed() {
+        let record = parse_epd(r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#)
+            .expect("valid EPD");
+        assert_eq!(record.board.to_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(record.best_moves, vec![Move::new(22, 46)]);
+        assert!(record.avoid_moves.is_empty());
+        assert_eq!(record.id.as_deref(), Some("WAC.001"));
+        assert_eq!(record.operations["bm"], ["Qg6"]);
+        assert_eq!(record.operations["id"], ["WAC.001"]);
+    }
+
+    #[test]
+    fn operations_set_the_clocks_and_avoid_moves() {
+        let record = parse_epd(r#"4k3/8/8/8/8/8/4P3/4K3 w - - am e3 Kd1; hmvc 7; fmvn 30; c0 "two words; one semicolon""#)
+            .expect("valid EPD");
+        assert_eq!(record.board.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 7 30");
+        assert_eq!(record.avoid_moves, vec![Move::new(12, 20), Move::new(4, 3)]);
+        assert_eq!(record.operations["c0"], ["two words; one semicolon"]);
+        assert_eq!(record.id, None);
+    }
+
+    #[test]
+    fn bad_lines_are_rejected() {
+        assert!(matches!(parse_epd("4k3/8/8/8/8/8/8/4K3 w -").err(), Some(EpdError::MissingFields)));
+        assert!(matches!(parse_epd("4k3/8/8/8/8/8/8/4K3 x - -").err(), Some(EpdError::Fen(_))));
+        assert!(matches!(parse_epd(r#"4k3/8/8/8/8/8/8/4K3 w - - id "open"#).err(), Some(EpdError::UnterminatedString)));
+        assert_eq!(
+            parse_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Qh5;").err(),
+            Some(EpdError::IllegalMove { opcode: "bm".to_string(), san: "Qh5".to_string() })
+        );
+    }
+}
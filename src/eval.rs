@@ -1,12 +1,21 @@
 // === Evaluation ===
 // Module owner: @mnovzrkb
 
+use crate::kpk;
+use crate::magic::{bishop_attacks, queen_attacks, rook_attacks};
+use crate::movegen::KNIGHT_ATTACKS;
 use crate::types::*;
 
-// Material values (centipawns)
-const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20000];
+// Endgame material values (centipawns), where pawns and rooks gain on the
+// midgame PIECE_VALUES
+pub const PIECE_VALUES_EG: [i32; 6] = [120, 300, 320, 530, 920, 20000];
 
-// Piece-square tables (white's perspective, flip for black)
+// Game phase weights per piece type; the start position totals MAX_PHASE
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
+// Piece-square tables, drawn with rank 8 on the top row as seen by white
+// (index with flip_square for white, directly for black)
 #[rustfmt::skip]
 const PAWN_PST: [i32; 64] = [
      0,  0,  0,  0,  0,  0,  0,  0,
@@ -79,26 +88,374 @@ const KING_PST: [i32; 64] = [
      20, 30, 10,  0,  0, 10, 30, 20,
 ];
 
-fn get_pst(piece: Piece) -> &'static [i32; 64] {
-    match piece {
-        Piece::Pawn => &PAWN_PST,
-        Piece::Knight => &KNIGHT_PST,
-        Piece::Bishop => &BISHOP_PST,
-        Piece::Rook => &ROOK_PST,
-        Piece::Queen => &QUEEN_PST,
-        Piece::King => &KING_PST,
-    }
-}
+// Endgame tables: pawns gain value as they advance, the king heads for the centre
+#[rustfmt::skip]
+const PAWN_PST_EG: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KING_PST_EG: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
 
 fn flip_square(sq: Square) -> Square {
     sq ^ 56
 }
 
-/// Evaluate position from side-to-move's perspective (centipawns)
-pub fn evaluate(board: &impl ChessBoard) -> i32 {
+/// Rank counted from `color`'s own back rank (0..7)
+fn relative_rank(color: Color, sq: Square) -> u8 {
+    match color {
+        Color::White => sq / 8,
+        Color::Black => 7 - sq / 8,
+    }
+}
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+/// Every square on each file
+const FILE_MASKS: [Bitboard; 8] = [
+    FILE_A,
+    FILE_A << 1,
+    FILE_A << 2,
+    FILE_A << 3,
+    FILE_A << 4,
+    FILE_A << 5,
+    FILE_A << 6,
+    FILE_A << 7,
+];
+
+/// Every square on the files either side of each file
+const ADJACENT_FILE_MASKS: [Bitboard; 8] = [
+    FILE_MASKS[1],
+    FILE_MASKS[0] | FILE_MASKS[2],
+    FILE_MASKS[1] | FILE_MASKS[3],
+    FILE_MASKS[2] | FILE_MASKS[4],
+    FILE_MASKS[3] | FILE_MASKS[5],
+    FILE_MASKS[4] | FILE_MASKS[6],
+    FILE_MASKS[5] | FILE_MASKS[7],
+    FILE_MASKS[6],
+];
+
+// Pawn structure penalties (midgame, endgame)
+const DOUBLED_PAWN: (i32, i32) = (-10, -20); // per pawn beyond the first on a file
+const ISOLATED_PAWN: (i32, i32) = (-10, -15);
+
+// Mobility bonus per reachable square (midgame, endgame), by piece type
+const MOBILITY_WEIGHTS: [(i32, i32); 6] = [(0, 0), (4, 4), (5, 5), (2, 4), (1, 2), (0, 0)];
+
+// Bonus for holding two or more bishops (midgame, endgame)
+const BISHOP_PAIR: (i32, i32) = (30, 50);
+
+// Rook placement bonuses (midgame, endgame)
+const ROOK_OPEN_FILE: (i32, i32) = (25, 10);
+const ROOK_HALF_OPEN_FILE: (i32, i32) = (12, 5);
+const ROOK_ON_SEVENTH: (i32, i32) = (20, 30);
+
+// King pawn shield, midgame only: per file next to the king
+const SHIELD_PAWN_NEAR: i32 = 12; // friendly pawn one rank ahead of the king
+const SHIELD_PAWN_FAR: i32 = 6; // friendly pawn two ranks ahead
+const SHIELD_PAWN_MISSING: i32 = -15;
+const OPEN_FILE_NEAR_KING: i32 = -15; // no friendly pawn anywhere on the file
+
+// Penalty for being in check at a leaf
+const IN_CHECK_PENALTY: i32 = 50;
+
+// Bonus for having the move
+const TEMPO: i32 = 12;
+
+// Won KPK endings: a sure win, plus a bonus per rank the pawn has advanced
+const KPK_WIN: i32 = 1000;
+const KPK_RANK_BONUS: i32 = 20;
+
+// KQvK and KRvK: drive the lone king toward a corner, the strong king after it
+const MATE_EDGE_PUSH: i32 = 40; // per step of the lone king away from the center
+const MATE_KING_CLOSE: i32 = 10; // per step the kings are closer than 7 apart
+
+// Passed pawn bonus by rank relative to the pawn's own side (index 1 = home rank)
+const PASSED_PAWN_MG: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const PASSED_PAWN_EG: [i32; 8] = [0, 10, 15, 25, 45, 70, 110, 0];
+
+/// Precomputed front spans: squares on the pawn's file and both neighbours,
+/// strictly ahead of it toward promotion, per color
+const fn precompute_passed_masks() -> [[Bitboard; 64]; 2] {
+    let mut masks = [[0u64; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let file = sq % 8;
+        let rank = sq / 8;
+        let mut other = 0;
+        while other < 64 {
+            let other_file = other % 8;
+            let other_rank = other / 8;
+            if other_file + 1 >= file && other_file <= file + 1 {
+                if other_rank > rank {
+                    masks[0][sq] |= 1u64 << other;
+                }
+                if other_rank < rank {
+                    masks[1][sq] |= 1u64 << other;
+                }
+            }
+            other += 1;
+        }
+        sq += 1;
+    }
+    masks
+}
+
+static PASSED_MASKS: [[Bitboard; 64]; 2] = precompute_passed_masks();
+
+/// Precomputed pawn shield zones: the king's file and both neighbours,
+/// one and two ranks toward the enemy, per color
+const fn precompute_shield_masks() -> [[Bitboard; 64]; 2] {
+    let mut masks = [[0u64; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let file = sq % 8;
+        let rank = sq / 8;
+        let mut other = 0;
+        while other < 64 {
+            let other_file = other % 8;
+            let other_rank = other / 8;
+            if other_file + 1 >= file && other_file <= file + 1 {
+                if other_rank > rank && other_rank <= rank + 2 {
+                    masks[0][sq] |= 1u64 << other;
+                }
+                if other_rank < rank && other_rank + 2 >= rank {
+                    masks[1][sq] |= 1u64 << other;
+                }
+            }
+            other += 1;
+        }
+        sq += 1;
+    }
+    masks
+}
+
+static SHIELD_MASKS: [[Bitboard; 64]; 2] = precompute_shield_masks();
+
+/// Every tunable evaluation weight. `EvalParams::DEFAULT` holds the values
+/// `evaluate` uses; `evaluate_with` takes any other set, e.g. for tuning.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    pub piece_values: [i32; 6],
+    pub piece_values_eg: [i32; 6],
+    /// Piece-square tables by piece type, laid out like the constant tables
+    pub pst: [[i32; 64]; 6],
+    pub pst_eg: [[i32; 64]; 6],
+    pub doubled_pawn: (i32, i32),
+    pub isolated_pawn: (i32, i32),
+    pub passed_pawn_mg: [i32; 8],
+    pub passed_pawn_eg: [i32; 8],
+    pub mobility: [(i32, i32); 6],
+    pub bishop_pair: (i32, i32),
+    pub rook_open_file: (i32, i32),
+    pub rook_half_open_file: (i32, i32),
+    pub rook_on_seventh: (i32, i32),
+    pub shield_pawn_near: i32,
+    pub shield_pawn_far: i32,
+    pub shield_pawn_missing: i32,
+    pub open_file_near_king: i32,
+    pub in_check: i32,
+    pub tempo: i32,
+}
+
+impl EvalParams {
+    pub const DEFAULT: EvalParams = EvalParams {
+        piece_values: PIECE_VALUES,
+        piece_values_eg: PIECE_VALUES_EG,
+        pst: [PAWN_PST, KNIGHT_PST, BISHOP_PST, ROOK_PST, QUEEN_PST, KING_PST],
+        pst_eg: [PAWN_PST_EG, KNIGHT_PST, BISHOP_PST, ROOK_PST, QUEEN_PST, KING_PST_EG],
+        doubled_pawn: DOUBLED_PAWN,
+        isolated_pawn: ISOLATED_PAWN,
+        passed_pawn_mg: PASSED_PAWN_MG,
+        passed_pawn_eg: PASSED_PAWN_EG,
+        mobility: MOBILITY_WEIGHTS,
+        bishop_pair: BISHOP_PAIR,
+        rook_open_file: ROOK_OPEN_FILE,
+        rook_half_open_file: ROOK_HALF_OPEN_FILE,
+        rook_on_seventh: ROOK_ON_SEVENTH,
+        shield_pawn_near: SHIELD_PAWN_NEAR,
+        shield_pawn_far: SHIELD_PAWN_FAR,
+        shield_pawn_missing: SHIELD_PAWN_MISSING,
+        open_file_near_king: OPEN_FILE_NEAR_KING,
+        in_check: IN_CHECK_PENALTY,
+        tempo: TEMPO,
+    };
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Pawn shield in front of each king still on its first two ranks, white's perspective (midgame)
+fn king_shield(board: &impl ChessBoard, params: &EvalParams) -> i32 {
     let mut score = 0;
+    for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        let king_bb = board.pieces(color, Piece::King);
+        if king_bb == 0 {
+            continue;
+        }
+        let king_sq = king_bb.trailing_zeros() as Square;
+        if relative_rank(color, king_sq) > 1 {
+            continue;
+        }
+
+        let own_pawns = board.pieces(color, Piece::Pawn);
+        let shield = SHIELD_MASKS[color as usize][king_sq as usize] & own_pawns;
+        let near_rank = match color {
+            Color::White => 0xFFu64 << (8 * (king_sq / 8 + 1)),
+            Color::Black => 0xFFu64 << (8 * (king_sq / 8 - 1)),
+        };
+        let king_file = (king_sq % 8) as usize;
+        for &file_mask in &FILE_MASKS[king_file.saturating_sub(1)..=(king_file + 1).min(7)] {
+            let file_shield = shield & file_mask;
+            if file_shield & near_rank != 0 {
+                score += sign * params.shield_pawn_near;
+            } else if file_shield != 0 {
+                score += sign * params.shield_pawn_far;
+            } else {
+                score += sign * params.shield_pawn_missing;
+                if own_pawns & file_mask == 0 {
+                    score += sign * params.open_file_near_king;
+                }
+            }
+        }
+    }
+    score
+}
+
+/// Passed pawn bonuses as (midgame, endgame), white's perspective
+fn passed_pawns(board: &impl ChessBoard, params: &EvalParams) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        let enemy_pawns = board.pieces(color.opposite(), Piece::Pawn);
+        for sq in BitIter(board.pieces(color, Piece::Pawn)) {
+            if PASSED_MASKS[color as usize][sq as usize] & enemy_pawns != 0 {
+                continue;
+            }
+            let rank = relative_rank(color, sq) as usize;
+            mg += sign * params.passed_pawn_mg[rank];
+            eg += sign * params.passed_pawn_eg[rank];
+        }
+    }
+    (mg, eg)
+}
 
-    // Material + piece-square tables
+/// Doubled and isolated pawn penalties as (midgame, endgame), white's perspective
+fn pawn_structure(board: &impl ChessBoard, params: &EvalParams) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        let pawns = board.pieces(color, Piece::Pawn);
+        for file in 0..8 {
+            let count = (pawns & FILE_MASKS[file]).count_ones() as i32;
+            if count == 0 {
+                continue;
+            }
+            mg += sign * (count - 1) * params.doubled_pawn.0;
+            eg += sign * (count - 1) * params.doubled_pawn.1;
+            if pawns & ADJACENT_FILE_MASKS[file] == 0 {
+                mg += sign * count * params.isolated_pawn.0;
+                eg += sign * count * params.isolated_pawn.1;
+            }
+        }
+    }
+    (mg, eg)
+}
+
+/// Rooks on open or half-open files and on the seventh rank as (midgame, endgame), white's perspective
+fn rook_placement(board: &impl ChessBoard, params: &EvalParams) -> (i32, i32) {
+    let all_pawns =
+        board.pieces(Color::White, Piece::Pawn) | board.pieces(Color::Black, Piece::Pawn);
+    let mut mg = 0;
+    let mut eg = 0;
+    for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        let own_pawns = board.pieces(color, Piece::Pawn);
+        for sq in BitIter(board.pieces(color, Piece::Rook)) {
+            let file = FILE_MASKS[(sq % 8) as usize];
+            if all_pawns & file == 0 {
+                mg += sign * params.rook_open_file.0;
+                eg += sign * params.rook_open_file.1;
+            } else if own_pawns & file == 0 {
+                mg += sign * params.rook_half_open_file.0;
+                eg += sign * params.rook_half_open_file.1;
+            }
+            if relative_rank(color, sq) == 6 {
+                mg += sign * params.rook_on_seventh.0;
+                eg += sign * params.rook_on_seventh.1;
+            }
+        }
+    }
+    (mg, eg)
+}
+
+/// Squares attacked by `color`'s pawns
+fn pawn_attacks(color: Color, pawns: Bitboard) -> Bitboard {
+    match color {
+        Color::White => ((pawns << 7) & !FILE_MASKS[7]) | ((pawns << 9) & !FILE_MASKS[0]),
+        Color::Black => ((pawns >> 9) & !FILE_MASKS[7]) | ((pawns >> 7) & !FILE_MASKS[0]),
+    }
+}
+
+/// Mobility of knights, bishops, rooks and queens as (midgame, endgame), white's perspective.
+/// Counts reachable squares not holding a friendly piece or covered by an enemy pawn.
+fn mobility(board: &impl ChessBoard, params: &EvalParams) -> (i32, i32) {
+    let all = board.occupancy(Color::White) | board.occupancy(Color::Black);
+    let mut mg = 0;
+    let mut eg = 0;
+    for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        let enemy_pawns = board.pieces(color.opposite(), Piece::Pawn);
+        let available = !board.occupancy(color) & !pawn_attacks(color.opposite(), enemy_pawns);
+        for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+            let (mg_weight, eg_weight) = params.mobility[piece as usize];
+            for sq in BitIter(board.pieces(color, piece)) {
+                let attacks = match piece {
+                    Piece::Knight => KNIGHT_ATTACKS[sq as usize],
+                    Piece::Bishop => bishop_attacks(sq, all),
+                    Piece::Rook => rook_attacks(sq, all),
+                    _ => queen_attacks(sq, all),
+                };
+                let count = (attacks & available).count_ones() as i32;
+                mg += sign * count * mg_weight;
+                eg += sign * count * eg_weight;
+            }
+        }
+    }
+    (mg, eg)
+}
+
+/// Game phase from remaining non-pawn material: MAX_PHASE at the start, 0 with bare kings and pawns
+pub fn game_phase(board: &impl ChessBoard) -> i32 {
+    let mut phase = 0;
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let count = board.piece_count(Color::White, piece) + board.piece_count(Color::Black, piece);
+        phase += count as i32 * PHASE_WEIGHTS[piece as usize];
+    }
+    phase.min(MAX_PHASE)
+}
+
+/// Material and piece-square scores as ((mg, eg), (mg, eg)), white's perspective
+fn material_and_psts(board: &impl ChessBoard, params: &EvalParams) -> ((i32, i32), (i32, i32)) {
+    let mut material = (0, 0);
+    let mut pst = (0, 0);
     for piece in [
         Piece::Pawn,
         Piece::Knight,
@@ -110,21 +467,167 @@ pub fn evaluate(board: &impl ChessBoard) -> i32 {
         let white_bb = board.pieces(Color::White, piece);
         let black_bb = board.pieces(Color::Black, piece);
 
-        let piece_val = PIECE_VALUES[piece as usize];
-        let pst = get_pst(piece);
+        let count = white_bb.count_ones() as i32 - black_bb.count_ones() as i32;
+        material.0 += count * params.piece_values[piece as usize];
+        material.1 += count * params.piece_values_eg[piece as usize];
 
+        let (mg_pst, eg_pst) = (&params.pst[piece as usize], &params.pst_eg[piece as usize]);
         for sq in BitIter(white_bb) {
-            score += piece_val + pst[sq as usize];
+            let idx = flip_square(sq) as usize;
+            pst.0 += mg_pst[idx];
+            pst.1 += eg_pst[idx];
         }
         for sq in BitIter(black_bb) {
-            score -= piece_val + pst[flip_square(sq) as usize];
+            let idx = sq as usize;
+            pst.0 -= mg_pst[idx];
+            pst.1 -= eg_pst[idx];
         }
     }
+    (material, pst)
+}
 
-    // King safety penalty if in check
+/// Bishop pair bonus as (midgame, endgame), white's perspective
+fn bishop_pair(board: &impl ChessBoard, params: &EvalParams) -> (i32, i32) {
+    let mut score = (0, 0);
+    for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        if board.piece_count(color, Piece::Bishop) >= 2 {
+            score.0 += sign * params.bishop_pair.0;
+            score.1 += sign * params.bishop_pair.1;
+        }
+    }
+    score
+}
+
+/// Every evaluation term as (name, midgame, endgame), white's perspective, before phase blending
+pub fn eval_terms(board: &impl ChessBoard, params: &EvalParams) -> [(&'static str, i32, i32); 8] {
+    let (material, pst) = material_and_psts(board, params);
+    let bishops = bishop_pair(board, params);
+    let rooks = rook_placement(board, params);
+    let passed = passed_pawns(board, params);
+    let structure = pawn_structure(board, params);
+    let mobility = mobility(board, params);
+    [
+        ("Material", material.0, material.1),
+        ("Piece-square", pst.0, pst.1),
+        ("Bishop pair", bishops.0, bishops.1),
+        ("Rooks", rooks.0, rooks.1),
+        // King safety only matters while there is material left to attack with
+        ("King shield", king_shield(board, params), 0),
+        ("Passed pawns", passed.0, passed.1),
+        ("Pawn structure", structure.0, structure.1),
+        ("Mobility", mobility.0, mobility.1),
+    ]
+}
+
+/// Penalty for the side in check, white's perspective
+pub fn check_penalty(board: &impl ChessBoard, params: &EvalParams) -> i32 {
     if board.is_in_check(board.side_to_move()) {
-        score -= 50 * if board.side_to_move() == Color::White { 1 } else { -1 };
+        -params.in_check * if board.side_to_move() == Color::White { 1 } else { -1 }
+    } else {
+        0
+    }
+}
+
+/// Bonus for the side to move, white's perspective
+pub fn tempo(board: &impl ChessBoard, params: &EvalParams) -> i32 {
+    if board.side_to_move() == Color::White {
+        params.tempo
+    } else {
+        -params.tempo
+    }
+}
+
+/// Blend midgame and endgame scores by phase so the endgame terms take over as material comes off
+pub fn blend(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+/// A position scorer the search can use in place of the built-in evaluation.
+/// Each search thread scores with its own clone.
+pub trait Evaluator: Clone + Send {
+    /// Score from the side to move's perspective, in centipawns
+    fn evaluate(&self, board: &impl ChessBoard) -> i32;
+}
+
+/// The built-in hand-written evaluation
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ClassicalEval;
+
+impl Evaluator for ClassicalEval {
+    fn evaluate(&self, board: &impl ChessBoard) -> i32 {
+        evaluate(board)
+    }
+}
+
+/// Exact score for king and pawn vs king (white's perspective), None for any
+/// other material
+pub fn kpk_score(board: &impl ChessBoard) -> Option<i32> {
+    let occupied = board.occupancy(Color::White) | board.occupancy(Color::Black);
+    let pawns = board.pieces(Color::White, Piece::Pawn) | board.pieces(Color::Black, Piece::Pawn);
+    if occupied.count_ones() != 3 || pawns.count_ones() != 1 {
+        return None;
+    }
+
+    // Look at it from the pawn's side, flipping ranks when black has the pawn
+    let strong = if board.pieces(Color::White, Piece::Pawn) != 0 { Color::White } else { Color::Black };
+    let weak = strong.opposite();
+    let orient = |sq: Square| if strong == Color::White { sq } else { flip_square(sq) };
+    let pawn = orient(pawns.trailing_zeros() as Square);
+    let strong_king = orient(board.pieces(strong, Piece::King).trailing_zeros() as Square);
+    let weak_king = orient(board.pieces(weak, Piece::King).trailing_zeros() as Square);
+
+    if !kpk::probe(strong_king, weak_king, pawn, board.side_to_move() == strong) {
+        return Some(0);
+    }
+    let score = KPK_WIN + KPK_RANK_BONUS * rank_of(pawn) as i32;
+    Some(if strong == Color::White { score } else { -score })
+}
+
+/// Score for king and queen or rook vs a lone king (white's perspective) that
+/// drives the lone king to a corner, None for any other material
+pub fn mating_score(board: &impl ChessBoard) -> Option<i32> {
+    let occupied = board.occupancy(Color::White) | board.occupancy(Color::Black);
+    if occupied.count_ones() != 3 {
+        return None;
     }
+    let heavy = |color| board.pieces(color, Piece::Queen) | board.pieces(color, Piece::Rook);
+    let strong = if heavy(Color::White) != 0 {
+        Color::White
+    } else if heavy(Color::Black) != 0 {
+        Color::Black
+    } else {
+        return None;
+    };
+    let piece = if board.pieces(strong, Piece::Queen) != 0 { Piece::Queen } else { Piece::Rook };
+
+    let strong_king = board.pieces(strong, Piece::King).trailing_zeros() as Square;
+    let weak_king = board.pieces(strong.opposite(), Piece::King).trailing_zeros() as Square;
+    // Chebyshev distance from the four center squares: 0 in the center, 3 on the edge
+    let from_center = |x: u8| if x < 4 { 3 - x } else { x - 4 };
+    let edge = from_center(file_of(weak_king)).max(from_center(rank_of(weak_king))) as i32;
+    let close = 7 - square_distance(strong_king, weak_king) as i32;
+
+    let score = PIECE_VALUES_EG[piece as usize] + MATE_EDGE_PUSH * edge + MATE_KING_CLOSE * close;
+    Some(if strong == Color::White { score } else { -score })
+}
+
+/// Evaluate position from side-to-move's perspective (centipawns)
+pub fn evaluate(board: &impl ChessBoard) -> i32 {
+    evaluate_with(board, &EvalParams::DEFAULT)
+}
+
+/// `evaluate` with custom weights
+pub fn evaluate_with(board: &impl ChessBoard, params: &EvalParams) -> i32 {
+    let score = match kpk_score(board).or_else(|| mating_score(board)) {
+        Some(score) => score,
+        None => {
+            // Midgame and endgame scores, white's perspective
+            let (mg, eg) = eval_terms(board, params)
+                .iter()
+                .fold((0, 0), |(mg, eg), &(_, term_mg, term_eg)| (mg + term_mg, eg + term_eg));
+            blend(mg, eg, game_phase(board)) + check_penalty(board, params) + tempo(board, params)
+        }
+    };
 
     // Return from side-to-move perspective
     if board.side_to_move() == Color::White {
@@ -133,3 +636,168 @@ pub fn evaluate(board: &impl ChessBoard) -> i32 {
         -score
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    fn fen(fen: &str) -> Board {
+        Board::try_from_fen(fen).expect("valid test FEN")
+    }
+
+    /// The blended terms, white's perspective, without the KPK and mating shortcuts
+    fn tapered(board: &Board) -> i32 {
+        let (mg, eg) = eval_terms(board, &EvalParams::DEFAULT)
+            .iter()
+            .fold((0, 0), |(mg, eg), &(_, term_mg, term_eg)| (mg + term_mg, eg + term_eg));
+        blend(mg, eg, game_phase(board))
+    }
+
+    #[test]
+    fn endgame_king_heads_for_the_center() {
+        let center = fen("7k/8/8/8/4K3/8/P7/8 w - - 0 1");
+        let back_rank = fen("7k/8/8/8/8/8/P7/4K3 w - - 0 1");
+        assert_eq!(game_phase(&center), 0);
+        assert_eq!(game_phase(&Board::new()), MAX_PHASE);
+        assert!(tapered(&center) > tapered(&back_rank));
+    }
+
+    #[test]
+    fn outside_passer_is_an_advantage() {
+        // Three pawns each, but only white's a-pawn has no enemy pawn in front
+        let white_passer = fen("6k1/5ppp/8/P7/8/8/6PP/6K1 b - - 0 1");
+        assert!(passed_pawns(&white_passer, &EvalParams::DEFAULT).1 > 0);
+        assert!(evaluate(&white_passer) < 0);
+        // The same structure with colours swapped favours black
+        let black_passer = fen("6k1/6pp/8/8/p7/8/5PPP/6K1 w - - 0 1");
+        assert!(passed_pawns(&black_passer, &EvalParams::DEFAULT).1 < 0);
+        assert!(evaluate(&black_passer) < 0);
+    }
+
+    #[test]
+    fn tripled_and_isolated_pawns_are_penalized() {
+        let structure = |f: &str| pawn_structure(&fen(f), &EvalParams::DEFAULT);
+
+        let tripled = structure("4k3/ppp5/8/8/2P5/2P5/2P5/4K3 w - - 0 1");
+        let sound = structure("4k3/ppp5/8/8/8/8/PPP5/4K3 w - - 0 1");
+        assert_eq!(sound, (0, 0));
+        assert!(tripled.0 < sound.0 && tripled.1 < sound.1);
+
+        let isolated_queen_pawn = structure("4k3/pp3ppp/4p3/8/3P4/8/PP3PPP/4K3 w - - 0 1");
+        let connected = structure("4k3/pp3ppp/4p3/8/3P4/2P5/PP4PP/4K3 w - - 0 1");
+        assert!(isolated_queen_pawn.0 < connected.0 && isolated_queen_pawn.1 < connected.1);
+    }
+
+    #[test]
+    fn opening_a_bishop_diagonal_adds_mobility() {
+        // e2-e3 opens the f1 bishop's diagonal (and the queen's); white is to move in both so tempo cancels
+        let closed = Board::new();
+        let open = fen("rnbqkbnr/pppppppp/8/8/8/4P3/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+        let params = &EvalParams::DEFAULT;
+        assert!(mobility(&open, params).0 > mobility(&closed, params).0);
+        assert!(evaluate(&open) > evaluate(&closed));
+    }
+
+    #[test]
+    fn bishop_pair_holder_scores_higher() {
+        // Black has given up the f8 bishop for a knight
+        let board = fen("2b1kn2/pppppppp/8/8/8/8/PPPPPPPP/2B1KB2 b - - 0 1");
+        assert_eq!(bishop_pair(&board, &EvalParams::DEFAULT), BISHOP_PAIR);
+        assert!(evaluate(&board) < 0);
+        let no_pair_bonus = EvalParams { bishop_pair: (0, 0), ..EvalParams::DEFAULT };
+        assert!(evaluate(&board) < evaluate_with(&board, &no_pair_bonus));
+    }
+
+    #[test]
+    fn rooks_like_open_files_and_the_seventh() {
+        let open_file = fen("6k1/ppp2ppp/8/8/8/8/PPP2PPP/3R2K1 w - - 0 1");
+        let closed_file = fen("6k1/ppp2ppp/8/8/8/8/PPP2PPP/R5K1 w - - 0 1");
+        let params = &EvalParams::DEFAULT;
+        assert!(rook_placement(&open_file, params).0 > rook_placement(&closed_file, params).0);
+        assert!(evaluate(&open_file) > evaluate(&closed_file));
+
+        let seventh = fen("6k1/pppR1ppp/8/8/8/8/PPP2PPP/6K1 w - - 0 1");
+        let sixth = fen("6k1/ppp2ppp/3R4/8/8/8/PPP2PPP/6K1 w - - 0 1");
+        assert!(rook_placement(&seventh, params).1 > rook_placement(&sixth, params).1);
+        assert!(evaluate(&seventh) > evaluate(&sixth));
+    }
+
+    #[test]
+    fn pawn_shield_protects_a_castled_king() {
+        let home = fen("r2q1rk1/5ppp/8/8/8/8/5PPP/R2Q1RK1 w - - 0 1");
+        let advanced = fen("r2q1rk1/5ppp/8/8/5PPP/8/8/R2Q1RK1 w - - 0 1");
+        let params = &EvalParams::DEFAULT;
+        assert!(king_shield(&home, params) > king_shield(&advanced, params));
+        assert!(evaluate(&home) > evaluate(&advanced));
+    }
+
+    #[test]
+    fn start_position_is_balanced() {
+        let board = Board::new();
+        for (name, mg, eg) in eval_terms(&board, &EvalParams::DEFAULT) {
+            assert_eq!((mg, eg), (0, 0), "{}", name);
+        }
+        // Only the side to move's tempo bonus breaks the symmetry
+        let no_tempo = EvalParams { tempo: 0, ..EvalParams::DEFAULT };
+        assert_eq!(evaluate_with(&board, &no_tempo), 0);
+        assert_eq!(evaluate(&board), TEMPO);
+    }
+
+    #[test]
+    fn default_params_match_evaluate() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "2r3k1/pp3ppp/4p3/3pP3/1P1n4/P2B4/5PPP/2R3K1 b - - 0 25",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "6k1/8/8/8/8/8/8/4K2Q b - - 0 1",
+        ];
+        for f in positions {
+            let board = fen(f);
+            assert_eq!(evaluate_with(&board, &EvalParams::default()), evaluate(&board), "{}", f);
+        }
+
+        // A changed weight moves the score by exactly its effect
+        let board = fen(positions[3]);
+        let params = EvalParams { tempo: EvalParams::DEFAULT.tempo + 10, ..EvalParams::DEFAULT };
+        assert_eq!(evaluate_with(&board, &params), evaluate(&board) + 10);
+    }
+
+    #[test]
+    fn kpk_positions_are_scored_exactly() {
+        // King on a key square in front of the pawn wins whoever moves
+        let win = KPK_WIN + 4 * KPK_RANK_BONUS;
+        assert_eq!(evaluate(&fen("3k4/8/3K4/3P4/8/8/8/8 w - - 0 1")), win);
+        assert_eq!(evaluate(&fen("3k4/8/3K4/3P4/8/8/8/8 b - - 0 1")), -win);
+        // The same with colors reversed
+        assert_eq!(evaluate(&fen("8/8/8/8/3p4/3k4/8/3K4 b - - 0 1")), win);
+
+        // Rook pawn with the defender in the corner: a7 only stalemates
+        assert_eq!(evaluate(&fen("k7/8/PK6/8/8/8/8/8 w - - 0 1")), 0);
+        assert_eq!(evaluate(&fen("k7/8/PK6/8/8/8/8/8 b - - 0 1")), 0);
+
+        // Opposition: with black to move the king must give way and the pawn
+        // queens; with white to move black keeps the opposition and draws
+        assert_eq!(evaluate(&fen("8/3k4/8/3K4/3P4/8/8/8 b - - 0 1")), -(KPK_WIN + 3 * KPK_RANK_BONUS));
+        assert_eq!(evaluate(&fen("8/3k4/8/3K4/3P4/8/8/8 w - - 0 1")), 0);
+    }
+
+    #[test]
+    fn side_to_move_is_worth_twice_the_tempo() {
+        let tempo = EvalParams::DEFAULT.tempo;
+        assert!((10..=15).contains(&tempo));
+        let positions = [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1",
+            "2r3k1/pp3ppp/4p3/3pP3/1P1n4/P2B4/5PPP/2R3K1",
+        ];
+        for placement in positions {
+            let white = fen(&format!("{} w - - 0 1", placement));
+            let black = fen(&format!("{} b - - 0 1", placement));
+            // Both from white's point of view
+            assert_eq!(evaluate(&white) - -evaluate(&black), 2 * tempo, "{}", placement);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// === King and Pawn vs King ===
+// Module owner: @mnovzrkb
+
+use crate::types::*;
+use std::sync::OnceLock;
+
+// The pawn's side is normalized to white, with the pawn on files a-d (the
+// board is mirrored otherwise) and ranks 2-7
+const PAWN_SQUARES: usize = 24;
+const POSITIONS: usize = 2 * 64 * 64 * PAWN_SQUARES;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+static BITBASE: OnceLock<Vec<Outcome>> = OnceLock::new();
+
+/// Whether the side with the pawn wins, with perfect play. Squares are given
+/// with white holding the pawn; `white_to_move` picks the side to move.
+pub fn probe(white_king: Square, black_king: Square, pawn: Square, white_to_move: bool) -> bool {
+    // Pawns on files e-h use the a-d tables, mirrored
+    let (wk, bk, p) = if file_of(pawn) > 3 {
+        (white_king ^ 7, black_king ^ 7, pawn ^ 7)
+    } else {
+        (white_king, black_king, pawn)
+    };
+    let db = BITBASE.get_or_init(build);
+    db[index(!white_to_move, wk, bk, p)] == Outcome::Win
+}
+
+fn index(black_to_move: bool, wk: Square, bk: Square, pawn: Square) -> usize {
+    let pawn_index = (rank_of(pawn) as usize - 1) * 4 + file_of(pawn) as usize;
+    ((black_to_move as usize * 64 + wk as usize) * 64 + bk as usize) * PAWN_SQUARES + pawn_index
+}
+
+/// Squares a king on `sq` can step to
+fn king_steps(sq: Square) -> impl Iterator<Item = Square> {
+    let (file, rank) = (file_of(sq) as i8, rank_of(sq) as i8);
+    (-1..=1i8)
+        .flat_map(move |df| (-1..=1i8).map(move |dr| (file + df, rank + dr)))
+        .filter(move |&(f, r)| (f, r) != (file, rank) && (0..8).contains(&f) && (0..8).contains(&r))
+        .map(|(f, r)| (r * 8 + f) as Square)
+}
+
+/// Squares a white pawn on `sq` attacks
+fn pawn_attacks(sq: Square) -> impl Iterator<Item = Square> {
+    let file = file_of(sq);
+    [(file > 0).then(|| sq + 7), (file < 7).then(|| sq + 9)].into_iter().flatten()
+}
+
+/// Outcomes decidable without looking ahead: illegal setups, immediate
+/// promotions, stalemates and the pawn falling
+fn initial(black_to_move: bool, wk: Square, bk: Square, pawn: Square) -> Outcome {
+    if wk == bk || wk == pawn || bk == pawn || square_distance(wk, bk) <= 1 {
+        return Outcome::Invalid;
+    }
+    if !black_to_move && pawn_attacks(pawn).any(|sq| sq == bk) {
+        return Outcome::Invalid; // black in check with white to move
+    }
+
+    if !black_to_move {
+        let queen = pawn + 8;
+        if rank_of(pawn) == 6 && wk != queen && (square_distance(bk, queen) > 1 || square_distance(wk, queen) == 1) {
+            return Outcome::Win; // promotes and the queen can't be taken
+        }
+    } else {
+        let safe = |sq: Square| square_distance(sq, wk) > 1 && !pawn_attacks(pawn).any(|a| a == sq);
+        if !king_steps(bk).any(safe) {
+            return Outcome::Draw; // stalemate (black isn't in check with a lone pawn here)
+        }
+        if square_distance(bk, pawn) == 1 && square_distance(wk, pawn) > 1 {
+            return Outcome::Draw; // the pawn falls
+        }
+    }
+    Outcome::Unknown
+}
+
+/// Combine the outcomes after each move: the side to move picks its best
+fn classify(db: &[Outcome], black_to_move: bool, wk: Square, bk: Square, pawn: Square) -> Outcome {
+    let mut any_unknown = false;
+    let (good, bad) = if black_to_move { (Outcome::Draw, Outcome::Win) } else { (Outcome::Win, Outcome::Draw) };
+    let mut children: Vec<Outcome> = Vec::with_capacity(10);
+
+    if black_to_move {
+        for to in king_steps(bk) {
+            if to == pawn {
+                continue; // undefended captures were settled up front
+            }
+            children.push(db[index(false, wk, to, pawn)]);
+        }
+    } else {
+        for to in king_steps(wk) {
+            if to != pawn {
+                children.push(db[index(true, to, bk, pawn)]);
+            }
+        }
+        let push = pawn + 8;
+        if rank_of(pawn) < 6 && push != wk && push != bk {
+            children.push(db[index(true, wk, bk, push)]);
+            let double = push + 8;
+            if rank_of(pawn) == 1 && double != wk && double != bk {
+                children.push(db[index(true, wk, bk, double)]);
+            }
+        }
+    }
+
+    for child in children {
+        match child {
+            c if c == good => return good,
+            Outcome::Unknown => any_unknown = true,
+            _ => {}
+        }
+    }
+    if any_unknown {
+        Outcome::Unknown
+    } else {
+        bad
+    }
+}
+
+/// Retrograde analysis: seed the known outcomes, then settle the rest until
+/// nothing changes. Whatever stays unknown can't be forced, so it's a draw.
+fn build() -> Vec<Outcome> {
+    let mut db = vec![Outcome::Invalid; POSITIONS];
+    let positions = || {
+        (0..2).flat_map(|stm| {
+            (0..64).flat_map(move |wk| {
+                (0..64).flat_map(move |bk| {
+                    (0..PAWN_SQUARES).map(move |p| (stm == 1, wk as Square, bk as Square, ((p / 4 + 1) * 8 + p % 4) as Square))
+                })
+            })
+        })
+    };
+    for (btm, wk, bk, pawn) in positions() {
+        db[index(btm, wk, bk, pawn)] = initial(btm, wk, bk, pawn);
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (btm, wk, bk, pawn) in positions() {
+            let i = index(btm, wk, bk, pawn);
+            if db[i] == Outcome::Unknown {
+                let outcome = classify(&db, btm, wk, bk, pawn);
+                if outcome != Outcome::Unknown {
+                    db[i] = outcome;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for outcome in db.iter_mut() {
+        if *outcome == Outcome::Unknown {
+            *outcome = Outcome::Draw;
+        }
+    }
+    db
+}
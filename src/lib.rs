@@ -3,16 +3,27 @@
 //
 // Module owners:
 // - types.rs, board.rs: @rea78sbq
-// - movegen.rs: @rpbr2qqf
-// - eval.rs: @mnovzrkb
-// - search.rs, uci.rs: @i3mjagsb
+// - movegen.rs, magic.rs, notation.rs, pgn.rs, epd.rs: @rpbr2qqf
+// - eval.rs, kpk.rs: @mnovzrkb
+// - book.rs, movepick.rs, polyglot.rs, search.rs, tablebase.rs, time.rs, tt.rs, uci.rs: @i3mjagsb
 
 pub mod types;
 pub mod board;
+pub mod magic;
 pub mod movegen;
+pub mod notation;
+pub mod pgn;
+pub mod epd;
 pub mod eval;
+pub mod kpk;
+pub mod book;
+pub mod polyglot;
 pub mod search;
+pub mod movepick;
+pub mod tablebase;
+pub mod time;
+pub mod tt;
 pub mod uci;
 
-pub use board::Board;
+pub use board::{Board, GameResult};
 pub use types::{ChessBoard, Color, Move, Piece, Square};
@@ -1,7 +1,10 @@
 // === Move Generation ===
 // Module owner: @rpbr2qqf
 
+use crate::board::squares_between;
+use crate::magic::{bishop_attacks, queen_attacks, rook_attacks};
 use crate::types::*;
+use std::ops::{Deref, DerefMut};
 
 /// Precomputed knight attack bitboards
 const fn precompute_knight_attacks() -> [Bitboard; 64] {
@@ -53,27 +56,243 @@ const fn precompute_king_attacks() -> [Bitboard; 64] {
     attacks
 }
 
-static KNIGHT_ATTACKS: [Bitboard; 64] = precompute_knight_attacks();
+pub(crate) static KNIGHT_ATTACKS: [Bitboard; 64] = precompute_knight_attacks();
 static KING_ATTACKS: [Bitboard; 64] = precompute_king_attacks();
 
+/// Room for every pseudo-legal move of any position (the most legal moves
+/// known in one position is 218)
+pub const MAX_MOVES: usize = 256;
+
+/// Fixed-capacity move list kept on the stack, so generating moves in the
+/// search doesn't allocate. Derefs to a slice for iteration, indexing and sorting.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        MoveList { moves: [Move::default(); MAX_MOVES], len: 0 }
+    }
+
+    pub fn push(&mut self, m: Move) {
+        self.moves[self.len] = m;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Keep only the first `len` moves
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Keep only the moves `keep` accepts, in order
+    pub fn retain(&mut self, mut keep: impl FnMut(Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Which promotions the generators emit
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GenMode {
+    /// Every move; what perft and anything needing exact move counts must use
+    #[default]
+    Full,
+    /// Promotions to queen and knight only. Rook and bishop promotions almost
+    /// never beat a queen, while a knight's fork or check sometimes does.
+    NoUnderpromotion,
+}
+
+impl GenMode {
+    fn promotions(self) -> &'static [Piece] {
+        match self {
+            GenMode::Full => &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight],
+            GenMode::NoUnderpromotion => &[Piece::Queen, Piece::Knight],
+        }
+    }
+}
+
+/// Legal moves for the side to move. Works on a copy of the board, so prefer
+/// `generate_legal` when a mutable board is at hand (search, perft).
 pub fn generate_moves(board: &impl ChessBoard) -> Vec<Move> {
-    let mut moves = Vec::with_capacity(256);
+    generate_legal(&mut board.clone())
+}
+
+/// Legal moves, checked by making and unmaking each candidate in place
+pub fn generate_legal(board: &mut impl ChessBoard) -> Vec<Move> {
+    let mut moves = MoveList::new();
+    generate_moves_into(board, GenMode::Full, &mut moves);
+    moves.to_vec()
+}
+
+/// `generate_legal` into a caller's list, replacing its contents, without allocating
+pub fn generate_moves_into(board: &mut impl ChessBoard, mode: GenMode, moves: &mut MoveList) {
     let us = board.side_to_move();
+    if board.is_in_check(us) {
+        generate_evasions_into(board, mode, moves);
+        return;
+    }
+    moves.clear();
+    let targets = !board.occupancy(us);
 
-    generate_pawn_moves(board, us, &mut moves);
-    generate_knight_moves(board, us, &mut moves);
-    generate_bishop_moves(board, us, &mut moves);
-    generate_rook_moves(board, us, &mut moves);
-    generate_queen_moves(board, us, &mut moves);
-    generate_king_moves(board, us, &mut moves);
-    generate_castling_moves(board, us, &mut moves);
+    generate_pawn_moves(board, us, PawnMoves::All, mode, moves);
+    generate_knight_moves(board, us, targets, moves);
+    generate_bishop_moves(board, us, targets, moves);
+    generate_rook_moves(board, us, targets, moves);
+    generate_queen_moves(board, us, targets, moves);
+    generate_king_moves(board, us, targets, moves);
+    generate_castling_moves(board, us, moves);
 
     // Filter to legal moves only
-    moves.retain(|m| is_legal(board, *m));
-    moves
+    moves.retain(|m| is_legal(board, m));
+}
+
+/// Legal replies to check. Works on a copy of the board, like `generate_moves`.
+pub fn generate_evasions(board: &impl ChessBoard) -> Vec<Move> {
+    let mut moves = MoveList::new();
+    generate_evasions_into(&mut board.clone(), GenMode::Full, &mut moves);
+    moves.to_vec()
 }
 
-fn generate_pawn_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+/// Check evasions into a caller's list: king steps to safe squares and, against
+/// a single checker, captures of it and blocks on its line. In double check only
+/// the king can move.
+pub fn generate_evasions_into(board: &mut impl ChessBoard, mode: GenMode, moves: &mut MoveList) {
+    moves.clear();
+    let us = board.side_to_move();
+    let them = us.opposite();
+    let king_bb = board.pieces(us, Piece::King);
+    if king_bb == 0 {
+        return;
+    }
+    let king = king_bb.trailing_zeros() as Square;
+    let occupied = board.occupancy(Color::White) | board.occupancy(Color::Black);
+    let checkers = board.attackers_through(king, them, occupied);
+
+    // Sliders see through the king's square once it steps away, so it can't
+    // retreat along the line of the check
+    for to in BitIter(KING_ATTACKS[king as usize] & !board.occupancy(us)) {
+        if board.attackers_through(to, them, occupied & !king_bb) == 0 {
+            moves.push(Move::new(king, to));
+        }
+    }
+    if checkers.count_ones() != 1 {
+        return;
+    }
+
+    // Everything else has to take the checker or step onto its line; pins are
+    // still caught by playing the move out
+    let checker = checkers.trailing_zeros() as Square;
+    let targets = checkers | squares_between(king, checker);
+    let ep_victim = board.en_passant_square().map(|sq| if us == Color::White { sq - 8 } else { sq + 8 });
+    generate_pawn_moves(board, us, PawnMoves::All, mode, moves);
+    generate_knight_moves(board, us, targets, moves);
+    generate_bishop_moves(board, us, targets, moves);
+    generate_rook_moves(board, us, targets, moves);
+    generate_queen_moves(board, us, targets, moves);
+    moves.retain(|m| {
+        m.from == king
+            || ((targets & (1u64 << m.to) != 0 || (m.is_en_passant() && ep_victim == Some(checker))) && is_legal(board, m))
+    });
+}
+
+/// Legal captures (including en passant) and promotions, for quiescence search
+pub fn generate_captures(board: &mut impl ChessBoard) -> Vec<Move> {
+    let mut moves = MoveList::new();
+    generate_captures_into(board, GenMode::Full, &mut moves);
+    moves.to_vec()
+}
+
+/// `generate_captures` into a caller's list, replacing its contents, without allocating
+pub fn generate_captures_into(board: &mut impl ChessBoard, mode: GenMode, moves: &mut MoveList) {
+    moves.clear();
+    let us = board.side_to_move();
+    let targets = board.occupancy(us.opposite());
+
+    generate_pawn_moves(board, us, PawnMoves::Noisy, mode, moves);
+    generate_knight_moves(board, us, targets, moves);
+    generate_bishop_moves(board, us, targets, moves);
+    generate_rook_moves(board, us, targets, moves);
+    generate_queen_moves(board, us, targets, moves);
+    generate_king_moves(board, us, targets, moves);
+
+    moves.retain(|m| is_legal(board, m));
+}
+
+/// Legal moves that are neither captures nor promotions, castling included:
+/// everything `generate_captures` leaves out
+pub fn generate_quiet_moves(board: &mut impl ChessBoard) -> Vec<Move> {
+    let mut moves = MoveList::new();
+    generate_quiet_moves_into(board, &mut moves);
+    moves.to_vec()
+}
+
+/// `generate_quiet_moves` into a caller's list, replacing its contents, without allocating
+pub fn generate_quiet_moves_into(board: &mut impl ChessBoard, moves: &mut MoveList) {
+    moves.clear();
+    let us = board.side_to_move();
+    let targets = !(board.occupancy(Color::White) | board.occupancy(Color::Black));
+
+    generate_pawn_moves(board, us, PawnMoves::Quiet, GenMode::Full, moves);
+    generate_knight_moves(board, us, targets, moves);
+    generate_bishop_moves(board, us, targets, moves);
+    generate_rook_moves(board, us, targets, moves);
+    generate_queen_moves(board, us, targets, moves);
+    generate_king_moves(board, us, targets, moves);
+    generate_castling_moves(board, us, moves);
+
+    moves.retain(|m| is_legal(board, m));
+}
+
+/// Which pawn moves to generate: captures and promotions are the noisy ones
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum PawnMoves {
+    All,
+    Noisy,
+    Quiet,
+}
+
+fn generate_pawn_moves(board: &impl ChessBoard, us: Color, kind: PawnMoves, mode: GenMode, moves: &mut MoveList) {
     let pawns = board.pieces(us, Piece::Pawn);
     let empty = !(board.occupancy(Color::White) | board.occupancy(Color::Black));
     let enemies = board.occupancy(us.opposite());
@@ -91,30 +310,29 @@ fn generate_pawn_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>
         // Single push
         if empty & to_mask != 0 {
             if promo_rank_mask & to_mask != 0 {
-                for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move {
-                        from,
-                        to,
-                        promotion: Some(promo),
-                        is_castle: false,
-                        is_en_passant: false,
-                    });
+                if kind != PawnMoves::Quiet {
+                    for &promo in mode.promotions() {
+                        moves.push(Move::with_kind(from, to, MoveKind::Promotion(promo)));
+                    }
                 }
-            } else {
-                moves.push(Move { from, to, promotion: None, is_castle: false, is_en_passant: false });
+            } else if kind != PawnMoves::Noisy {
+                moves.push(Move::new(from, to));
 
                 // Double push from starting rank
                 let from_mask = 1u64 << from;
                 if start_rank_mask & from_mask != 0 {
                     let double_to = (to as i8 + push_dir) as Square;
                     if double_to < 64 && empty & (1u64 << double_to) != 0 {
-                        moves.push(Move { from, to: double_to, promotion: None, is_castle: false, is_en_passant: false });
+                        moves.push(Move::with_kind(from, double_to, MoveKind::DoublePush));
                     }
                 }
             }
         }
 
         // Captures (including en passant)
+        if kind == PawnMoves::Quiet {
+            continue;
+        }
         let capture_dirs: &[i8] = match us {
             Color::White => &[7, 9],
             Color::Black => &[-7, -9],
@@ -122,7 +340,7 @@ fn generate_pawn_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>
 
         for &dir in capture_dirs {
             let cap_to = from as i8 + dir;
-            if cap_to < 0 || cap_to >= 64 { continue; }
+            if !(0..64).contains(&cap_to) { continue; }
             let cap_to = cap_to as Square;
 
             // Check for file wrap
@@ -135,101 +353,84 @@ fn generate_pawn_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>
             // Normal capture
             if enemies & cap_mask != 0 {
                 if promo_rank_mask & cap_mask != 0 {
-                    for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                        moves.push(Move {
-                            from,
-                            to: cap_to,
-                            promotion: Some(promo),
-                            is_castle: false,
-                            is_en_passant: false,
-                        });
+                    for &promo in mode.promotions() {
+                        moves.push(Move::with_kind(from, cap_to, MoveKind::CapturePromotion(promo)));
                     }
                 } else {
-                    moves.push(Move { from, to: cap_to, promotion: None, is_castle: false, is_en_passant: false });
+                    moves.push(Move::new(from, cap_to));
                 }
             }
-            // En passant capture
+            // En passant capture. Emitted pseudo-legally: is_legal plays the full move,
+            // removing both pawns from the rank, so a rook or queen pinning them
+            // against the king along that rank is caught there
             else if let Some(ep_sq) = board.en_passant_square() {
                 if cap_to == ep_sq {
-                    moves.push(Move {
-                        from,
-                        to: cap_to,
-                        promotion: None,
-                        is_castle: false,
-                        is_en_passant: true,
-                    });
+                    moves.push(Move::with_kind(from, cap_to, MoveKind::EnPassant));
                 }
             }
         }
     }
 }
 
-fn generate_knight_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+fn generate_knight_moves(board: &impl ChessBoard, us: Color, valid_targets: Bitboard, moves: &mut MoveList) {
     let knights = board.pieces(us, Piece::Knight);
-    let valid_targets = !board.occupancy(us);
 
     for from in BitIter(knights) {
         let attacks = KNIGHT_ATTACKS[from as usize] & valid_targets;
         for to in BitIter(attacks) {
-            moves.push(Move { from, to, promotion: None, is_castle: false, is_en_passant: false });
+            moves.push(Move::new(from, to));
         }
     }
 }
 
-fn generate_bishop_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+fn generate_bishop_moves(board: &impl ChessBoard, us: Color, valid_targets: Bitboard, moves: &mut MoveList) {
     let bishops = board.pieces(us, Piece::Bishop);
-    let valid_targets = !board.occupancy(us);
     let all_pieces = board.occupancy(Color::White) | board.occupancy(Color::Black);
 
     for from in BitIter(bishops) {
-        let attacks = sliding_attacks(from, all_pieces, true) & valid_targets;
+        let attacks = bishop_attacks(from, all_pieces) & valid_targets;
         for to in BitIter(attacks) {
-            moves.push(Move { from, to, promotion: None, is_castle: false, is_en_passant: false });
+            moves.push(Move::new(from, to));
         }
     }
 }
 
-fn generate_rook_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+fn generate_rook_moves(board: &impl ChessBoard, us: Color, valid_targets: Bitboard, moves: &mut MoveList) {
     let rooks = board.pieces(us, Piece::Rook);
-    let valid_targets = !board.occupancy(us);
     let all_pieces = board.occupancy(Color::White) | board.occupancy(Color::Black);
 
     for from in BitIter(rooks) {
-        let attacks = sliding_attacks(from, all_pieces, false) & valid_targets;
+        let attacks = rook_attacks(from, all_pieces) & valid_targets;
         for to in BitIter(attacks) {
-            moves.push(Move { from, to, promotion: None, is_castle: false, is_en_passant: false });
+            moves.push(Move::new(from, to));
         }
     }
 }
 
-fn generate_queen_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+fn generate_queen_moves(board: &impl ChessBoard, us: Color, valid_targets: Bitboard, moves: &mut MoveList) {
     let queens = board.pieces(us, Piece::Queen);
-    let valid_targets = !board.occupancy(us);
     let all_pieces = board.occupancy(Color::White) | board.occupancy(Color::Black);
 
     for from in BitIter(queens) {
-        let attacks = (sliding_attacks(from, all_pieces, true)
-            | sliding_attacks(from, all_pieces, false))
-            & valid_targets;
+        let attacks = queen_attacks(from, all_pieces) & valid_targets;
         for to in BitIter(attacks) {
-            moves.push(Move { from, to, promotion: None, is_castle: false, is_en_passant: false });
+            moves.push(Move::new(from, to));
         }
     }
 }
 
-fn generate_king_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+fn generate_king_moves(board: &impl ChessBoard, us: Color, valid_targets: Bitboard, moves: &mut MoveList) {
     let king = board.pieces(us, Piece::King);
-    let valid_targets = !board.occupancy(us);
 
     for from in BitIter(king) {
         let attacks = KING_ATTACKS[from as usize] & valid_targets;
         for to in BitIter(attacks) {
-            moves.push(Move { from, to, promotion: None, is_castle: false, is_en_passant: false });
+            moves.push(Move::new(from, to));
         }
     }
 }
 
-fn generate_castling_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<Move>) {
+fn generate_castling_moves(board: &impl ChessBoard, us: Color, moves: &mut MoveList) {
     let rights = board.castling_rights();
     let all_pieces = board.occupancy(Color::White) | board.occupancy(Color::Black);
     let enemy = us.opposite();
@@ -239,82 +440,93 @@ fn generate_castling_moves(board: &impl ChessBoard, us: Color, moves: &mut Vec<M
         return;
     }
 
-    match us {
-        Color::White => {
-            // White kingside (e1-g1)
-            if rights & WHITE_KINGSIDE != 0 {
-                // Check squares between king and rook are empty
-                if all_pieces & 0x60 == 0 {  // f1, g1
-                    // Check king doesn't pass through or end up in check
-                    if !board.is_square_attacked(F1, enemy) && !board.is_square_attacked(G1, enemy) {
-                        moves.push(Move { from: E1, to: G1, promotion: None, is_castle: true, is_en_passant: false });
-                    }
-                }
-            }
-            // White queenside (e1-c1)
-            if rights & WHITE_QUEENSIDE != 0 {
-                // Check squares between king and rook are empty
-                if all_pieces & 0x0E == 0 {  // b1, c1, d1
-                    // Check king doesn't pass through or end up in check
-                    if !board.is_square_attacked(D1, enemy) && !board.is_square_attacked(C1, enemy) {
-                        moves.push(Move { from: E1, to: C1, promotion: None, is_castle: true, is_en_passant: false });
-                    }
-                }
-            }
-        }
-        Color::Black => {
-            // Black kingside (e8-g8)
-            if rights & BLACK_KINGSIDE != 0 {
-                if all_pieces & 0x6000000000000000 == 0 {  // f8, g8
-                    if !board.is_square_attacked(F8, enemy) && !board.is_square_attacked(G8, enemy) {
-                        moves.push(Move { from: E8, to: G8, promotion: None, is_castle: true, is_en_passant: false });
-                    }
-                }
-            }
-            // Black queenside (e8-c8)
-            if rights & BLACK_QUEENSIDE != 0 {
-                if all_pieces & 0x0E00000000000000 == 0 {  // b8, c8, d8
-                    if !board.is_square_attacked(D8, enemy) && !board.is_square_attacked(C8, enemy) {
-                        moves.push(Move { from: E8, to: C8, promotion: None, is_castle: true, is_en_passant: false });
-                    }
-                }
-            }
-        }
+    let king_bb = board.pieces(us, Piece::King);
+    if king_bb == 0 {
+        return;
     }
-}
+    let king_from = king_bb.trailing_zeros() as Square;
 
-/// Simple ray-based sliding piece attacks
-fn sliding_attacks(sq: Square, blockers: Bitboard, diagonal: bool) -> Bitboard {
-    let mut attacks = 0u64;
-    let directions: &[(i8, i8)] = if diagonal {
-        &[(1, 1), (1, -1), (-1, 1), (-1, -1)]
-    } else {
-        &[(0, 1), (0, -1), (1, 0), (-1, 0)]
+    let (back_rank, sides) = match us {
+        Color::White => (0, [(WHITE_KINGSIDE, G1, F1), (WHITE_QUEENSIDE, C1, D1)]),
+        Color::Black => (56, [(BLACK_KINGSIDE, G8, F8), (BLACK_QUEENSIDE, C8, D8)]),
     };
+    for (right, king_to, rook_to) in sides {
+        if rights & right == 0 {
+            continue;
+        }
+        let rook_from = board.castling_rook(right);
+        if board.pieces(us, Piece::Rook) & (1u64 << rook_from) == 0 || rank_of(king_from) * 8 != back_rank {
+            continue;
+        }
 
-    for &(dr, df) in directions {
-        let mut r = (sq / 8) as i8;
-        let mut f = (sq % 8) as i8;
-        loop {
-            r += dr;
-            f += df;
-            if r < 0 || r > 7 || f < 0 || f > 7 {
-                break;
-            }
-            let target = (r * 8 + f) as Square;
-            attacks |= 1u64 << target;
-            if blockers & (1u64 << target) != 0 {
-                break;
-            }
+        // Every square either piece travels over or lands on must be empty,
+        // apart from the king and castling rook themselves (Chess960 overlaps)
+        let others = all_pieces & !(1u64 << king_from) & !(1u64 << rook_from);
+        if (rank_span(king_from, king_to) | rank_span(rook_from, rook_to)) & others != 0 {
+            continue;
         }
+
+        // The king may not pass through or land on an attacked square
+        let king_path = rank_span(king_from, king_to) & !(1u64 << king_from);
+        if BitIter(king_path).any(|sq| board.is_square_attacked(sq, enemy)) {
+            continue;
+        }
+
+        moves.push(Move::with_kind(king_from, king_to, MoveKind::Castle));
     }
-    attacks
 }
 
-fn is_legal(board: &impl ChessBoard, m: Move) -> bool {
-    let mut test_board = board.clone();
-    test_board.make_move(m);
-    !test_board.is_in_check(board.side_to_move())
+/// Squares from `a` to `b` inclusive along a rank
+fn rank_span(a: Square, b: Square) -> Bitboard {
+    let (lo, hi) = (a.min(b), a.max(b));
+    (u64::MAX >> (63 - hi)) & (u64::MAX << lo)
+}
+
+/// A move is legal if it doesn't leave our own king in check
+fn is_legal(board: &mut impl ChessBoard, m: Move) -> bool {
+    let us = board.side_to_move();
+    board.make_move(m);
+    let legal = !board.is_in_check(us);
+    board.unmake_move();
+    legal
+}
+
+/// Whether `m` is a legal move here, without generating the whole move list.
+/// For moves from elsewhere (the TT, killers) that may not fit the position.
+pub fn is_legal_move(board: &mut impl ChessBoard, m: Move) -> bool {
+    let us = board.side_to_move();
+    let Some((piece, color)) = board.piece_at(m.from) else {
+        return false;
+    };
+    if color != us || (board.occupancy(us) & (1u64 << m.to) != 0 && !m.is_castle()) {
+        return false;
+    }
+
+    // Pawn moves and castling come in several kinds; check against the real list
+    let mut candidates = MoveList::new();
+    let pseudo_legal = match piece {
+        Piece::Pawn => {
+            generate_pawn_moves(board, us, PawnMoves::All, GenMode::Full, &mut candidates);
+            candidates.contains(&m)
+        }
+        Piece::King if m.is_castle() => {
+            generate_castling_moves(board, us, &mut candidates);
+            candidates.contains(&m)
+        }
+        _ if m.kind != MoveKind::Normal => false,
+        _ => {
+            let occupied = board.occupancy(Color::White) | board.occupancy(Color::Black);
+            let attacks = match piece {
+                Piece::Knight => KNIGHT_ATTACKS[m.from as usize],
+                Piece::Bishop => bishop_attacks(m.from, occupied),
+                Piece::Rook => rook_attacks(m.from, occupied),
+                Piece::Queen => queen_attacks(m.from, occupied),
+                _ => KING_ATTACKS[m.from as usize],
+            };
+            attacks & (1u64 << m.to) != 0
+        }
+    };
+    pseudo_legal && is_legal(board, m)
 }
 
 /// Perft: count leaf nodes at given depth (for testing)
@@ -322,7 +534,8 @@ pub fn perft(board: &mut impl ChessBoard, depth: u8) -> u64 {
     if depth == 0 {
         return 1;
     }
-    let moves = generate_moves(board);
+    let mut moves = MoveList::new();
+    generate_moves_into(board, GenMode::Full, &mut moves);
     if depth == 1 {
         return moves.len() as u64;
     }
@@ -337,3 +550,350 @@ pub fn perft(board: &mut impl ChessBoard, depth: u8) -> u64 {
         })
         .sum()
 }
+
+/// Leaf counts from `perft_stats`, matching the columns of the standard perft tables
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64, // including en passant
+    pub en_passant: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub checkmates: u64,
+}
+
+/// Perft that also classifies the moves leading to each leaf
+pub fn perft_stats(board: &mut impl ChessBoard, depth: u8) -> PerftStats {
+    let mut stats = PerftStats::default();
+    if depth == 0 {
+        stats.nodes = 1;
+    } else {
+        collect_perft_stats(board, depth, &mut stats);
+    }
+    stats
+}
+
+fn collect_perft_stats(board: &mut impl ChessBoard, depth: u8, stats: &mut PerftStats) {
+    for m in generate_legal(board) {
+        if depth == 1 {
+            stats.nodes += 1;
+            stats.captures += board.is_capture(m) as u64;
+            stats.en_passant += m.is_en_passant() as u64;
+            stats.castles += m.is_castle() as u64;
+            stats.promotions += m.promotion().is_some() as u64;
+        }
+        board.make_move(m);
+        if depth == 1 {
+            if board.is_in_check(board.side_to_move()) {
+                stats.checks += 1;
+                stats.checkmates += generate_legal(board).is_empty() as u64;
+            }
+        } else {
+            collect_perft_stats(board, depth - 1, stats);
+        }
+        board.unmake_move();
+    }
+}
+
+/// Perft split by root move: each legal move with the node count of its subtree
+pub fn perft_divide(board: &mut impl ChessBoard, depth: u8) -> Vec<(Move, u64)> {
+    generate_legal(board)
+        .into_iter()
+        .map(|m| {
+            board.make_move(m);
+            let count = perft(board, depth.saturating_sub(1));
+            board.unmake_move();
+            (m, count)
+        })
+        .collect()
+}
+
+/// A perft suite entry whose count didn't match, or a line that couldn't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftFailure {
+    pub line: usize, // 1-based
+    pub fen: String,
+    pub depth: u8,
+    pub expected: u64,
+    /// None if the FEN or a depth entry couldn't be parsed, or no depths were given
+    pub found: Option<u64>,
+}
+
+/// Run every `FEN ;D1 n1 ;D2 n2 ...` line of a perft suite file
+pub fn run_perft_suite(path: &str) -> std::io::Result<Vec<PerftFailure>> {
+    let suite = std::fs::read_to_string(path)?;
+    Ok(run_perft_suite_str(&suite))
+}
+
+/// `run_perft_suite` on the suite's text. Blank lines and `#` comments are
+/// skipped; any other line that can't be parsed, or lists no depths, is a
+/// failure with `found` None.
+pub fn run_perft_suite_str(suite: &str) -> Vec<PerftFailure> {
+    let mut failures = Vec::new();
+    for (i, line) in suite.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split(';');
+        let fen = fields.next().unwrap_or("").trim();
+        let failure = |depth, expected, found| PerftFailure { line: i + 1, fen: fen.to_string(), depth, expected, found };
+        let Ok(mut board) = crate::board::Board::try_from_fen(fen) else {
+            failures.push(failure(0, 0, None));
+            continue;
+        };
+
+        let mut entries = fields.peekable();
+        if entries.peek().is_none() {
+            failures.push(failure(0, 0, None));
+        }
+        for entry in entries {
+            let parsed = entry.trim().strip_prefix('D').and_then(|e| {
+                let (depth, count) = e.split_once(char::is_whitespace)?;
+                Some((depth.parse::<u8>().ok()?, count.trim().parse::<u64>().ok()?))
+            });
+            match parsed {
+                Some((depth, expected)) => {
+                    let found = perft(&mut board, depth);
+                    if found != expected {
+                        failures.push(failure(depth, expected, Some(found)));
+                    }
+                }
+                None => failures.push(failure(0, 0, None)),
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    fn fen(fen: &str) -> Board {
+        Board::try_from_fen(fen).expect("valid test FEN")
+    }
+
+    /// Moves in UCI notation, sorted, for comparing move sets
+    fn sorted(moves: &[Move]) -> Vec<String> {
+        let mut moves: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn captures_are_the_noisy_subset_of_legal_moves() {
+        // Kiwipete with a pawn on b7 and an en passant capture available
+        let mut board = fen("r3k2r/1P1pqpb1/bn2pnp1/2pPN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq c6 0 2");
+        let noisy: Vec<Move> = generate_moves(&board)
+            .into_iter()
+            .filter(|&m| board.is_capture(m) || m.promotion().is_some())
+            .collect();
+        let captures = generate_captures(&mut board);
+        assert!(captures.iter().any(|m| m.is_en_passant()));
+        assert!(captures.iter().any(|&m| m.promotion().is_some() && board.is_capture(m)));
+        assert_eq!(sorted(&captures), sorted(&noisy));
+    }
+
+    /// The old legality filter: play each pseudo-legal move on a fresh copy of the board
+    fn legal_by_cloning(board: &Board) -> Vec<Move> {
+        let us = board.side_to_move();
+        let targets = !board.occupancy(us);
+        let mut pseudo = MoveList::new();
+        generate_pawn_moves(board, us, PawnMoves::All, GenMode::Full, &mut pseudo);
+        generate_knight_moves(board, us, targets, &mut pseudo);
+        generate_bishop_moves(board, us, targets, &mut pseudo);
+        generate_rook_moves(board, us, targets, &mut pseudo);
+        generate_queen_moves(board, us, targets, &mut pseudo);
+        generate_king_moves(board, us, targets, &mut pseudo);
+        generate_castling_moves(board, us, &mut pseudo);
+        pseudo
+            .iter()
+            .copied()
+            .filter(|&m| {
+                let mut copy = board.clone();
+                copy.make_move(m);
+                !copy.is_in_check(us)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn in_place_legality_matches_cloning() {
+        for position in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // In check, and in double check
+            "4k3/8/8/8/1b6/8/8/R3K2R w KQ - 0 1",
+            "4k3/8/8/8/1b6/8/4r3/R3K2R w KQ - 0 1",
+        ] {
+            let mut board = fen(position);
+            assert_eq!(sorted(&generate_legal(&mut board)), sorted(&legal_by_cloning(&board)), "{}", position);
+            assert_eq!(board.to_fen(), fen(position).to_fen());
+        }
+    }
+
+    #[test]
+    fn divide_of_the_start_position() {
+        let mut board = Board::new();
+        let divide = perft_divide(&mut board, 2);
+        assert_eq!(divide.len(), 20);
+        assert!(divide.iter().all(|&(_, count)| count == 20));
+        assert_eq!(divide.iter().map(|&(_, count)| count).sum::<u64>(), perft(&mut board, 2));
+    }
+
+    #[test]
+    fn en_passant_never_exposes_the_king_along_the_rank() {
+        // Both pawns leave the king's rank at once, opening it to the rook
+        for (position, ep) in [("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1", "e5d6"), ("7K/8/8/8/k2Pp2R/8/8/8 b - d3 0 1", "e4d3")] {
+            let mut board = fen(position);
+            assert!(!sorted(&generate_legal(&mut board)).contains(&ep.to_string()), "{}", position);
+            assert!(!sorted(&generate_captures(&mut board)).contains(&ep.to_string()), "{}", position);
+        }
+        // Without the rook the capture is fine
+        let mut board = fen("8/8/8/K2pP3/8/8/8/7k w - d6 0 1");
+        assert!(generate_legal(&mut board).iter().any(|m| m.is_en_passant()));
+        // Capturing en passant removes a checking pawn
+        let mut board = fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+        assert!(generate_legal(&mut board).iter().any(|m| m.is_en_passant()));
+    }
+
+    #[test]
+    fn en_passant_heavy_perft() {
+        let mut board = fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+        let counts = [14, 191, 2812, 43238];
+        for (depth, &expected) in (1..).zip(counts.iter()) {
+            assert_eq!(perft(&mut board, depth), expected, "depth {}", depth);
+        }
+        assert_eq!(perft_stats(&mut board, 4).en_passant, 123);
+    }
+
+    #[test]
+    fn kiwipete_perft_stats() {
+        let mut board = fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let published = [
+            PerftStats { nodes: 48, captures: 8, en_passant: 0, castles: 2, promotions: 0, checks: 0, checkmates: 0 },
+            PerftStats { nodes: 2039, captures: 351, en_passant: 1, castles: 91, promotions: 0, checks: 3, checkmates: 0 },
+            PerftStats { nodes: 97862, captures: 17102, en_passant: 45, castles: 3162, promotions: 0, checks: 993, checkmates: 1 },
+        ];
+        for (depth, expected) in (1..).zip(published) {
+            assert_eq!(perft_stats(&mut board, depth), expected, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn move_list_generation_matches_the_vec_version() {
+        // One list reused across positions, as the search does
+        let mut list = MoveList::new();
+        for position in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "4k3/8/8/8/1b6/8/8/R3K2R w KQ - 0 1",
+            "7k/8/8/8/8/8/8/K7 w - - 0 1",
+        ] {
+            let mut board = fen(position);
+            generate_moves_into(&mut board, GenMode::Full, &mut list);
+            let vec = generate_moves(&board);
+            assert_eq!(list.len(), vec.len(), "{}", position);
+            assert!(list.iter().eq(vec.iter()), "{}", position);
+            for (i, &m) in vec.iter().enumerate() {
+                assert_eq!(list[i], m);
+            }
+        }
+    }
+
+    #[test]
+    fn evasions_in_single_and_double_check() {
+        // Bishop check: the king steps aside, the knight or rook blocks, the rook captures
+        let board = fen("4k3/8/8/8/1b6/8/1R6/1N2K2R w K - 0 1");
+        let evasions = generate_evasions(&board);
+        assert_eq!(sorted(&evasions), ["b1c3", "b1d2", "b2b4", "b2d2", "e1d1", "e1e2", "e1f1", "e1f2"]);
+        assert_eq!(sorted(&evasions), sorted(&legal_by_cloning(&board)));
+
+        // Adding a rook check makes it double check: only the king may move
+        let board = fen("4k3/8/8/8/1b6/8/1R6/1N2K2r w - - 0 1");
+        let evasions = generate_evasions(&board);
+        assert_eq!(sorted(&evasions), ["e1e2", "e1f2"]);
+        assert_eq!(sorted(&evasions), sorted(&legal_by_cloning(&board)));
+        assert_eq!(sorted(&generate_moves(&board)), sorted(&evasions));
+    }
+
+    #[test]
+    fn captures_and_quiets_make_up_the_legal_moves() {
+        for position in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/1P1pqpb1/bn2pnp1/2pPN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq c6 0 2",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut board = fen(position);
+            let captures = generate_captures(&mut board);
+            let quiets = generate_quiet_moves(&mut board);
+            assert!(quiets.iter().all(|m| !captures.contains(m)), "{}", position);
+            let mut both = sorted(&captures);
+            both.extend(sorted(&quiets));
+            both.sort();
+            assert_eq!(both, sorted(&generate_legal(&mut board)), "{}", position);
+        }
+    }
+
+    #[test]
+    fn gen_mode_picks_the_promotion_pieces() {
+        // The a7 pawn can promote on a8 or by taking on b8
+        let mut board = fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        let mut moves = MoveList::new();
+        for (mode, pieces) in [
+            (GenMode::Full, vec![Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight]),
+            (GenMode::NoUnderpromotion, vec![Piece::Queen, Piece::Knight]),
+        ] {
+            generate_moves_into(&mut board, mode, &mut moves);
+            let promotions: Vec<Move> = moves.iter().copied().filter(|m| m.promotion().is_some()).collect();
+            assert_eq!(promotions.len(), 2 * pieces.len(), "{:?}", mode);
+            for to in ["a8", "b8"] {
+                let mut promoted: Vec<Piece> =
+                    promotions.iter().filter(|m| square_to_algebraic(m.to) == to).filter_map(|m| m.promotion()).collect();
+                promoted.sort_by_key(|&p| p as usize);
+                let mut expected = pieces.clone();
+                expected.sort_by_key(|&p| p as usize);
+                assert_eq!(promoted, expected, "{:?} on {}", mode, to);
+            }
+            generate_captures_into(&mut board, mode, &mut moves);
+            assert_eq!(moves.iter().filter(|m| m.promotion().is_some()).count(), 2 * pieces.len(), "{:?}", mode);
+        }
+        assert_eq!(perft(&mut board, 1), generate_legal(&mut board).len() as u64);
+        assert_eq!(generate_legal(&mut board).iter().filter(|m| m.promotion().is_some()).count(), 8);
+    }
+
+    #[test]
+    fn perft_suite_runs_and_reports_bad_lines() {
+        let suite = "\
+# start position and kiwipete
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400 ;D3 8902
+
+r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 ;D1 48 ;D2 2039
+";
+        assert_eq!(run_perft_suite_str(suite), []);
+
+        let suite = "\
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 401
+not a fen ;D1 20
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 twenty
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+";
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let failure = |line, fen: &str, depth, expected, found| PerftFailure { line, fen: fen.to_string(), depth, expected, found };
+        assert_eq!(
+            run_perft_suite_str(suite),
+            [failure(1, start, 2, 401, Some(400)), failure(2, "not a fen", 0, 0, None), failure(3, start, 0, 0, None), failure(4, start, 0, 0, None)]
+        );
+
+        assert!(run_perft_suite("/nonexistent/perft.epd").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// === Move Picker ===
+// Module owner: @i3mjagsb
+
+use crate::movegen::{generate_captures_into, generate_quiet_moves_into, is_legal_move, GenMode, MoveList, MAX_MOVES};
+use crate::search::{score_move, see};
+use crate::types::*;
+
+/// Stages a `MovePicker` goes through, in order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Yields the legal moves of a position best-first, generating each stage only
+/// when the previous one runs out: the TT move, captures and promotions that
+/// don't lose material (MVV-LVA order), killers, quiet moves by history, and
+/// finally captures that lose material by SEE. A cutoff on an early move
+/// saves generating the rest.
+///
+/// The board is passed to each `next` call rather than held, since the search
+/// makes and unmakes moves on it between calls.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    killer_index: usize,
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    index: usize,
+    bad_captures: MoveList,
+}
+
+impl MovePicker {
+    pub fn new(tt_move: Option<Move>, killers: [Option<Move>; 2]) -> Self {
+        MovePicker {
+            stage: Stage::TtMove,
+            tt_move,
+            killers,
+            killer_index: 0,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            index: 0,
+            bad_captures: MoveList::new(),
+        }
+    }
+
+    /// The next move to search, or None once every legal move has been given.
+    /// `history` orders the quiet moves ([from][to], higher first).
+    pub fn next(&mut self, board: &mut impl ChessBoard, history: &[[i32; 64]; 64]) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    match self.tt_move {
+                        Some(m) if is_legal_move(board, m) => return Some(m),
+                        _ => self.tt_move = None,
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    generate_captures_into(board, GenMode::Full, &mut self.moves);
+                    let (tt_move, mut kept) = (self.tt_move, 0);
+                    for i in 0..self.moves.len() {
+                        let m = self.moves[i];
+                        if Some(m) == tt_move {
+                            continue;
+                        }
+                        if board.is_capture(m) && see(board, m) < 0 {
+                            self.bad_captures.push(m);
+                        } else {
+                            self.moves[kept] = m;
+                            self.scores[kept] = score_move(board, m) + m.promotion().map_or(0, |p| PIECE_VALUES[p as usize]);
+                            kept += 1;
+                        }
+                    }
+                    self.moves.truncate(kept);
+                    self.index = 0;
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.pick_best() {
+                    Some(m) => return Some(m),
+                    None => self.stage = Stage::Killers,
+                },
+                Stage::Killers => {
+                    let Some(&killer) = self.killers.get(self.killer_index) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    self.killer_index += 1;
+                    // Killers come from sibling positions, so may not even be legal here
+                    if let Some(m) = killer {
+                        let quiet = !board.is_capture(m) && m.promotion().is_none();
+                        if quiet && Some(m) != self.tt_move && is_legal_move(board, m) {
+                            return Some(m);
+                        }
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    generate_quiet_moves_into(board, &mut self.moves);
+                    let mut kept = 0;
+                    for i in 0..self.moves.len() {
+                        let m = self.moves[i];
+                        if Some(m) == self.tt_move || self.killers.contains(&Some(m)) {
+                            continue;
+                        }
+                        self.moves[kept] = m;
+                        self.scores[kept] = history[m.from as usize][m.to as usize];
+                        kept += 1;
+                    }
+                    self.moves.truncate(kept);
+                    self.index = 0;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.pick_best() {
+                    Some(m) => return Some(m),
+                    None => {
+                        self.index = 0;
+                        self.stage = Stage::BadCaptures;
+                    }
+                },
+                Stage::BadCaptures => {
+                    let m = self.bad_captures.get(self.index).copied();
+                    self.index += 1;
+                    if m.is_none() {
+                        self.stage = Stage::Done;
+                    }
+                    return m;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Swap the best-scored remaining move of the current stage to the front
+    /// and return it; a partial selection sort, since a cutoff usually comes
+    /// before the list is used up
+    fn pick_best(&mut self) -> Option<Move> {
+        if self.index >= self.moves.len() {
+            return None;
+        }
+        // Reversed so ties go to the earliest move, as a stable sort would
+        let best = (self.index..self.moves.len()).rev().max_by_key(|&i| self.scores[i]).expect("non-empty range");
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.index += 1;
+        Some(self.moves[self.index - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::movegen::generate_legal;
+
+    fn uci(board: &mut Board, text: &str) -> Move {
+        generate_legal(board).into_iter().find(|m| m.to_string() == text).expect("legal test move")
+    }
+
+    fn drain(board: &mut Board, tt_move: Option<Move>, killers: [Option<Move>; 2]) -> Vec<Move> {
+        let history = [[0; 64]; 64];
+        let mut picker = MovePicker::new(tt_move, killers);
+        std::iter::from_fn(|| picker.next(board, &history)).collect()
+    }
+
+    fn sorted(moves: &[Move]) -> Vec<String> {
+        let mut moves: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn picker_yields_every_legal_move_once_tt_move_first() {
+        let mut board = Board::try_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .expect("valid test FEN");
+        let legal = sorted(&generate_legal(&mut board));
+        let quiet_tt = uci(&mut board, "a2a3");
+        let capture_tt = uci(&mut board, "e5f7");
+        let castle = uci(&mut board, "e1g1");
+        // Blocked by the white pawn on a2, so illegal here
+        let illegal = Move::new(0, 56);
+
+        for (tt_move, killers) in [
+            (Some(quiet_tt), [Some(castle), Some(illegal)]),
+            (Some(capture_tt), [None, Some(quiet_tt)]),
+            (Some(illegal), [Some(castle), None]),
+            (None, [None, None]),
+        ] {
+            let picked = drain(&mut board, tt_move, killers);
+            assert_eq!(picked.len(), legal.len());
+            assert_eq!(sorted(&picked), legal);
+            if let Some(m) = tt_move.filter(|&m| m != illegal) {
+                assert_eq!(picked[0], m);
+            }
+        }
+    }
+}
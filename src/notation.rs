@@ -0,0 +1,214 @@
+// === Move Notation ===
+// Module owner: @rpbr2qqf
+
+use crate::movegen::generate_moves;
+use crate::types::*;
+
+fn piece_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+/// Format a legal move in Standard Algebraic Notation (e.g. "Nf3", "exd5", "O-O", "e8=Q+")
+pub fn move_to_san(board: &impl ChessBoard, m: Move) -> String {
+    let mut san = if m.is_castle() {
+        // The castling king always lands on the g- or c-file, even in Chess960
+        if file_of(m.to) == 6 { "O-O" } else { "O-O-O" }.to_string()
+    } else {
+        let (piece, _) = board.piece_at(m.from).expect("move_to_san: no piece on from square");
+        let capture = board.is_capture(m);
+        let mut san = String::new();
+
+        if piece == Piece::Pawn {
+            if capture {
+                san.push_str(&square_to_algebraic(m.from)[..1]);
+            }
+        } else {
+            san.push(piece_letter(piece));
+            san.push_str(&disambiguation(board, m, piece));
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&square_to_algebraic(m.to));
+        if let Some(promo) = m.promotion() {
+            san.push('=');
+            san.push(piece_letter(promo));
+        }
+        san
+    };
+
+    // Check and mate suffixes come from the resulting position
+    let mut after = board.clone();
+    after.make_move(m);
+    if after.is_in_check(after.side_to_move()) {
+        san.push(if generate_moves(&after).is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+/// Minimal origin hint when another piece of the same type can also reach the target:
+/// the file if it is unique, else the rank, else both
+fn disambiguation(board: &impl ChessBoard, m: Move, piece: Piece) -> String {
+    let rivals: Vec<Square> = generate_moves(board)
+        .into_iter()
+        .filter(|other| other.to == m.to && other.from != m.from)
+        .filter(|other| matches!(board.piece_at(other.from), Some((p, _)) if p == piece))
+        .map(|other| other.from)
+        .collect();
+
+    let name = square_to_algebraic(m.from);
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|&sq| sq % 8 != m.from % 8) {
+        name[..1].to_string()
+    } else if rivals.iter().all(|&sq| sq / 8 != m.from / 8) {
+        name[1..].to_string()
+    } else {
+        name
+    }
+}
+
+fn piece_from_letter(c: char) -> Option<Piece> {
+    match c {
+        'N' => Some(Piece::Knight),
+        'B' => Some(Piece::Bishop),
+        'R' => Some(Piece::Rook),
+        'Q' => Some(Piece::Queen),
+        'K' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+/// Parse a SAN move against the position, matching it to exactly one legal move.
+/// Trailing "+", "#", "!" and "?" are ignored; returns None if illegal or ambiguous.
+pub fn san_to_move(board: &impl ChessBoard, san: &str) -> Option<Move> {
+    let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal = generate_moves(board);
+
+    // Castling (letter O or digit zero)
+    let castle = match san {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(kingside) = castle {
+        return legal
+            .into_iter()
+            .find(|m| m.is_castle() && (file_of(m.to) == 6) == kingside);
+    }
+
+    // Promotion suffix: "e8=Q" or "e8Q"
+    let (body, promotion) = match san.char_indices().last() {
+        Some((i, c)) if piece_from_letter(c).is_some() && i >= 2 => {
+            (san[..i].trim_end_matches('='), piece_from_letter(c))
+        }
+        _ => (san, None),
+    };
+
+    // Leading piece letter, pawn otherwise
+    let (piece, body) = match body.chars().next().and_then(piece_from_letter) {
+        Some(piece) => (piece, &body[1..]),
+        None => (Piece::Pawn, body),
+    };
+
+    // Destination is the last two characters; whatever precedes it is disambiguation
+    let body: String = body.chars().filter(|&c| c != 'x').collect();
+    if body.len() < 2 || !body.is_ascii() {
+        return None;
+    }
+    let (hint, dest) = body.split_at(body.len() - 2);
+    let to = square_from_algebraic(dest)?;
+    if hint.len() > 2 {
+        return None;
+    }
+
+    let mut hint_file = None;
+    let mut hint_rank = None;
+    for c in hint.bytes() {
+        match c {
+            b'a'..=b'h' => hint_file = Some(c - b'a'),
+            b'1'..=b'8' => hint_rank = Some(c - b'1'),
+            _ => return None,
+        }
+    }
+
+    let mut matches = legal.into_iter().filter(|m| {
+        m.to == to
+            && m.promotion() == promotion
+            && matches!(board.piece_at(m.from), Some((p, _)) if p == piece)
+            && hint_file.is_none_or(|f| m.from % 8 == f)
+            && hint_rank.is_none_or(|r| m.from / 8 == r)
+    });
+    let m = matches.next()?;
+    if matches.next().is_some() {
+        return None;
+    }
+    Some(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    /// SAN of the legal move written `uci` in the position `fen`
+    fn san(fen: &str, uci: &str) -> String {
+        let board = Board::try_from_fen(fen).expect("valid test FEN");
+        let m = generate_moves(&board).into_iter().find(|m| m.to_string() == uci).expect("legal test move");
+        move_to_san(&board, m)
+    }
+
+    #[test]
+    fn san_disambiguates_minimally() {
+        assert_eq!(san("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2"), "Nbd2");
+        assert_eq!(san("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1", "a1b3"), "N1b3");
+        assert_eq!(san("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+        // A second knight that is pinned can't reach the square, so no disambiguation
+        assert_eq!(san("4k3/8/8/8/8/8/8/1N2KN1r w - - 0 1", "b1d2"), "Nd2");
+    }
+
+    #[test]
+    fn san_for_pawns_castling_and_mate() {
+        assert_eq!(san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", "e4d5"), "exd5");
+        assert_eq!(san("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", "e5f6"), "exf6");
+        assert_eq!(san("8/4P3/8/7k/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+        assert_eq!(san("8/4P3/8/7k/8/8/8/4K3 w - - 0 1", "e7e8n"), "e8=N");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"), "O-O");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"), "O-O-O");
+        assert_eq!(san("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7"), "Qxf7#");
+    }
+
+    #[test]
+    fn san_round_trips_every_legal_move() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/1P1pqpb1/bn2pnp1/2pPN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq c6 0 2",
+            "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let board = Board::try_from_fen(fen).expect("valid test FEN");
+            for m in generate_moves(&board) {
+                let san = move_to_san(&board, m);
+                assert_eq!(san_to_move(&board, &san), Some(m), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn san_parsing_rejects_bad_input() {
+        let board = Board::try_from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").expect("valid test FEN");
+        assert!(san_to_move(&board, "Nbd2+!?").is_some());
+        assert_eq!(san_to_move(&board, "Nd2"), None); // two knights can go there
+        assert_eq!(san_to_move(&board, "Nd3"), None); // neither can
+        assert_eq!(san_to_move(&board, "O-O"), None);
+        assert_eq!(san_to_move(&board, ""), None);
+    }
+}
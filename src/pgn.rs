@@ -0,0 +1,228 @@
+// === PGN Import/Export ===
+// Module owner: @rpbr2qqf
+
+use crate::board::{Board, FenError};
+use crate::movegen::generate_moves;
+use crate::notation::{move_to_san, san_to_move};
+use crate::types::*;
+
+const STARTPOS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// PGN export format keeps movetext lines under 80 characters
+const MAX_LINE_LEN: usize = 79;
+
+/// Export a game as PGN: the seven-tag roster (unknown values as placeholders),
+/// SetUp/FEN tags for a non-standard start, and numbered SAN movetext ending in the result
+pub fn export_pgn(start: &Board, moves: &[Move]) -> String {
+    let mut board = start.clone();
+    let mut tokens = Vec::with_capacity(moves.len() * 3 / 2 + 1);
+    for (i, &m) in moves.iter().enumerate() {
+        let number = board.fullmove_number();
+        if board.side_to_move() == Color::White {
+            tokens.push(format!("{}.", number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(move_to_san(&board, m));
+        board.make_move(m);
+    }
+    let result = game_result(&board);
+    tokens.push(result.to_string());
+
+    let mut pgn = String::new();
+    for (tag, value) in [
+        ("Event", "?"),
+        ("Site", "?"),
+        ("Date", "????.??.??"),
+        ("Round", "?"),
+        ("White", "?"),
+        ("Black", "?"),
+        ("Result", result),
+    ] {
+        pgn.push_str(&format!("[{} \"{}\"]\n", tag, value));
+    }
+    let start_fen = start.to_fen();
+    if start_fen != STARTPOS_FEN {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", start_fen));
+    }
+    pgn.push('\n');
+
+    // Movetext, wrapped between tokens
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > MAX_LINE_LEN {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+        line_len += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+    pgn
+}
+
+/// Result token for a finished position, "*" while the game is still going
+fn game_result(board: &Board) -> &'static str {
+    if !generate_moves(board).is_empty() {
+        return "*";
+    }
+    match (board.is_in_check(board.side_to_move()), board.side_to_move()) {
+        (true, Color::White) => "0-1",
+        (true, Color::Black) => "1-0",
+        (false, _) => "1/2-1/2",
+    }
+}
+
+/// Why a PGN game couldn't be played out
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    /// The FEN tag isn't a valid position
+    InvalidFen(FenError),
+    /// A SAN token in the movetext that doesn't match exactly one legal move
+    IllegalMove {
+        ply: usize, // 0-based index into the movetext's moves
+        san: String,
+    },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidFen(e) => write!(f, "invalid FEN tag: {}", e),
+            PgnError::IllegalMove { ply, san } => write!(f, "illegal or ambiguous move '{}' at ply {}", san, ply + 1),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// Play a PGN game out on a board. Starts from the FEN tag if present, else the
+/// standard position. Comments, NAGs, move numbers and variations are skipped.
+pub fn import_pgn(pgn: &str) -> Result<(Board, Vec<Move>), PgnError> {
+    let mut fen = None;
+    let mut sans = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    let mut token = String::new();
+    let mut variation_depth = 0;
+
+    while let Some(c) = chars.next() {
+        let is_delimiter = c.is_whitespace() || "[{;()$".contains(c);
+        if is_delimiter && !token.is_empty() {
+            if variation_depth == 0 {
+                sans.extend(movetext_token(&token));
+            }
+            token.clear();
+        }
+        match c {
+            '[' => {
+                let tag: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                if let Some(("FEN", value)) = tag.trim().split_once(char::is_whitespace) {
+                    fen = Some(value.trim().trim_matches('"').to_string());
+                }
+            }
+            '{' => chars.by_ref().take_while(|&c| c != '}').for_each(drop),
+            ';' => chars.by_ref().take_while(|&c| c != '\n').for_each(drop),
+            '(' => variation_depth += 1,
+            ')' => variation_depth = (variation_depth - 1).max(0),
+            '$' => {
+                while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+            }
+            _ if c.is_whitespace() => {}
+            _ => token.push(c),
+        }
+    }
+    if !token.is_empty() && variation_depth == 0 {
+        sans.extend(movetext_token(&token));
+    }
+
+    let mut board = match fen {
+        Some(fen) => Board::try_from_fen(&fen).map_err(PgnError::InvalidFen)?,
+        None => Board::new(),
+    };
+    let mut moves = Vec::with_capacity(sans.len());
+    for (ply, san) in sans.into_iter().enumerate() {
+        let m = san_to_move(&board, &san).ok_or(PgnError::IllegalMove { ply, san })?;
+        board.make_move(m);
+        moves.push(m);
+    }
+    Ok((board, moves))
+}
+
+/// The SAN part of a movetext token, dropping move numbers ("12.", "12...", "12.e4")
+/// and result markers
+fn movetext_token(token: &str) -> Option<String> {
+    if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return None;
+    }
+    // Only digits followed by a dot are a move number; "0-0" is castling
+    let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let san = match digits.strip_prefix('.') {
+        Some(rest) if digits.len() < token.len() => rest.trim_start_matches('.'),
+        _ => token,
+    };
+    (!san.is_empty()).then(|| san.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Play `sans` from `start`, returning the moves
+    fn moves_from(start: &Board, sans: &[&str]) -> Vec<Move> {
+        let mut board = start.clone();
+        sans.iter()
+            .map(|san| {
+                let m = san_to_move(&board, san).expect("legal test move");
+                board.make_move(m);
+                m
+            })
+            .collect()
+    }
+
+    #[test]
+    fn export_scholars_mate() {
+        let start = Board::new();
+        let moves = moves_from(&start, &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+        let pgn = export_pgn(&start, &moves);
+        assert!(pgn.starts_with("[Event \"?\"]\n"));
+        assert!(pgn.contains("[Result \"1-0\"]\n"));
+        assert!(!pgn.contains("[FEN"));
+        assert!(pgn.ends_with("\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"), "{}", pgn);
+    }
+
+    #[test]
+    fn export_from_a_black_move_numbers_with_an_ellipsis() {
+        let start = Board::try_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").expect("valid test FEN");
+        let moves = moves_from(&start, &["e5", "Nf3"]);
+        let pgn = export_pgn(&start, &moves);
+        assert!(pgn.contains("[SetUp \"1\"]\n"));
+        assert!(pgn.ends_with("\n\n1... e5 2. Nf3 *\n"), "{}", pgn);
+    }
+
+    #[test]
+    fn import_a_short_game() {
+        // Legal's mate
+        let pgn = "[Event \"Paris\"]\n[Result \"1-0\"]\n\n\
+            1. e4 e5 2. Nf3 d6 3. Bc4 Bg4 {the pin} 4. Nc3 g6?! (4... Nc6) 5. Nxe5 $1 Bxd1 \
+            6. Bxf7+ Ke7 7. Nd5# 1-0";
+        let (board, moves) = import_pgn(pgn).expect("valid PGN");
+        assert_eq!(moves.len(), 13);
+        assert_eq!(board.to_fen(), "rn1q1bnr/ppp1kB1p/3p2p1/3NN3/4P3/8/PPPP1PPP/R1BbK2R b KQ - 2 7");
+        assert!(board.is_checkmate());
+    }
+
+    #[test]
+    fn import_reports_the_failing_move_or_fen() {
+        let error = import_pgn("1. e4 e5 2. Ke3").err();
+        assert_eq!(error, Some(PgnError::IllegalMove { ply: 2, san: "Ke3".to_string() }));
+
+        let error = import_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1 extra\"]\n\n1. e4").err();
+        assert!(matches!(error, Some(PgnError::InvalidFen(_))));
+        let error = import_pgn("[FEN \"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1\"]\n\n1. e4").err();
+        assert_eq!(error, Some(PgnError::InvalidFen(FenError::RankCount(7))));
+    }
+}
@@ -1,64 +1,1627 @@
 // === Search ===
 // Module owner: @i3mjagsb
 
-use crate::eval::evaluate;
-use crate::movegen::generate_moves;
+use crate::board::Board;
+use crate::eval::{game_phase, ClassicalEval, Evaluator};
+use crate::movegen::{generate_captures_into, generate_legal, GenMode, MoveList};
+use crate::movepick::MovePicker;
+use crate::tablebase::{NoTablebase, Tablebase, Wdl};
+use crate::tt::{Bound, TranspositionTable};
 use crate::types::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 const INF: i32 = 100_000;
 
-/// Find best move at given depth
+/// Deepest iteration attempted when only a time limit is given
+pub const MAX_DEPTH: u8 = 64;
+
+// How often (in nodes) the clock and stop flag are checked
+const TIME_CHECK_INTERVAL: u64 = 2048;
+
+/// Deepest ply tracked by per-ply tables (killers)
+pub const MAX_PLY: usize = 64;
+
+/// Most search threads `set_threads` accepts
+pub const MAX_THREADS: usize = 256;
+
+// Root moves are reported to the currmove callback from this depth on
+const CURRMOVE_MIN_DEPTH: u8 = 5;
+
+// Scores this close to INF are mates; the gap is the distance in plies from the root
+const MATE_RANGE: i32 = 2 * MAX_PLY as i32;
+
+// Tablebase wins score above any static evaluation but below every mate, less
+// the ply so nearer wins are preferred
+const TB_WIN: i32 = 20_000;
+
+// Move ordering scores: captures, then killers, then other quiet moves
+const MVV_LVA_BASE: i32 = 1_000_000;
+const KILLER_SCORES: [i32; 2] = [900_000, 800_000];
+
+// History scores are halved across the table once any entry reaches this,
+// keeping them below the killer scores
+const HISTORY_MAX: i32 = 100_000;
+
+// Null-move pruning: depth reduction and the minimum depth to try it at
+const NULL_MOVE_R: u8 = 2;
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+
+// Reverse futility pruning: up to this depth, a node whose static eval beats
+// beta by the margin per ply of depth is taken as a cutoff
+const RFP_MAX_DEPTH: u8 = 3;
+const RFP_MARGIN: i32 = 120;
+
+// Futility pruning: up to this depth, quiet moves are skipped when the static
+// eval plus the margin per ply of depth can't reach alpha
+const FUTILITY_MAX_DEPTH: u8 = 2;
+const FUTILITY_MARGIN: i32 = 150;
+
+// Late move pruning: up to this depth, quiet moves past a count that grows
+// with depth are skipped at non-PV nodes
+const LMP_MAX_DEPTH: u8 = 3;
+
+// Late move reductions apply from this move index and depth onward
+const LMR_MIN_INDEX: usize = 4;
+const LMR_MIN_DEPTH: u8 = 3;
+
+// Delta pruning: quiescence skips captures that can't lift the score to alpha even
+// with this margin, unless the game phase has dropped to the limit (few pieces
+// left, where a single pawn can decide the game)
+const DELTA_MARGIN: i32 = 200;
+const DELTA_MIN_PHASE: i32 = 4;
+
+// Aspiration windows: initial half-width around the previous score, doubled on
+// each fail, and given up for a full window once it grows past the limit
+const ASPIRATION_WINDOW: i32 = 50;
+const ASPIRATION_MAX_WINDOW: i32 = 800;
+
+/// Find best move at given depth, deepening one ply at a time
 pub fn search(board: &mut impl ChessBoard, depth: u8) -> (Move, i32) {
-    let mut best_move = None;
-    let mut best_score = -INF;
+    let result = search_ex(board, depth);
+    (result.best_move, result.score)
+}
 
-    for m in generate_moves(board) {
-        board.make_move(m);
-        let score = -negamax(board, depth - 1, -INF, INF);
-        board.unmake_move();
+/// `search` with the full result: depth reached, nodes and principal variation
+pub fn search_ex(board: &mut impl ChessBoard, depth: u8) -> SearchResult {
+    let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+    SearchState::new().search_ex(board, &limits)
+}
 
-        if score > best_score {
-            best_score = score;
-            best_move = Some(m);
-        }
+/// When to stop deepening. Unset fields impose no limit.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    /// Clock budget: no new iteration starts once half of it is used
+    pub time: Option<Duration>,
+    /// Fixed time per move: searched right up to the limit
+    pub movetime: Option<Duration>,
+    /// Node budget: the search stops once this many nodes are visited
+    pub nodes: Option<u64>,
+    /// Number of best root moves to report (0 is treated as 1)
+    pub multipv: usize,
+}
+
+/// A scored principal variation starting from the root
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    pub score: i32,
+    pub moves: Vec<Move>,
+}
+
+/// Outcome of a search, from its deepest completed iteration
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    /// Deepest ply reached, quiescence included
+    pub seldepth: u8,
+    pub nodes: u64,
+    /// Principal variation, starting with `best_move`
+    pub pv: Vec<Move>,
+    /// Moves to mate for a proven mate score, negative when being mated
+    pub mate: Option<i32>,
+}
+
+/// Progress after a completed iteration, passed to the info callback
+pub struct SearchInfo<'a> {
+    pub depth: u8,
+    /// Deepest ply reached, quiescence included
+    pub seldepth: usize,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// Transposition table fill, per thousand
+    pub hashfull: u32,
+    pub lines: &'a [PvLine],
+}
+
+/// Counters from the last search, for diagnostics
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SearchStats {
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+    /// Beta cutoffs, and how many of them came from the first move tried
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+    /// Root searches repeated because the score fell outside the aspiration window
+    pub aspiration_researches: u64,
+}
+
+/// Search techniques that can be switched off, to measure what each is worth
+#[derive(Copy, Clone, Debug)]
+struct Features {
+    null_move: bool,
+    check_extension: bool,
+    lmr: bool,
+    pvs: bool,
+    rfp: bool,
+    futility: bool,
+    lmp: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self { null_move: true, check_extension: true, lmr: true, pvs: true, rfp: true, futility: true, lmp: true }
     }
-    (best_move.expect("no legal moves"), best_score)
 }
 
-/// Negamax with alpha-beta pruning
-fn negamax(board: &mut impl ChessBoard, depth: u8, mut alpha: i32, beta: i32) -> i32 {
-    if depth == 0 {
-        return evaluate(board);
+type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+type CurrmoveCallback = Box<dyn FnMut(u8, Move, usize) + Send>;
+
+/// Move ordering state carried through a search, and the evaluator it scores leaves with
+pub struct SearchState<E: Evaluator = ClassicalEval> {
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    history: [[i32; 64]; 64], // [from][to] bonus for quiet cutoff moves
+    skip_null: bool,          // set by the parent to forbid two null moves in a row
+    root_depth: u8,           // nominal depth of the current iteration
+    completed_depth: u8,      // deepest fully searched iteration
+    seldepth: usize,          // deepest ply reached in the current iteration
+    lines: Vec<PvLine>,       // MultiPV results of that iteration
+    root_excluded: Vec<Move>, // root moves already reported this iteration
+    nodes: u64,
+    stats: SearchStats,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    clock_start: Instant,  // when the time limits started counting
+    time_limits: (Option<Duration>, Option<Duration>), // (budget, movetime) of the current search
+    stop: Arc<AtomicBool>, // raised by another thread to end the search early
+    ponder: Arc<AtomicBool>, // cleared by another thread on ponderhit
+    pondering: bool,       // time limits are held back until the ponder flag is cleared
+    stopped: bool,         // set once the deadline passes or stop is raised; the iteration is abandoned
+    tt: Arc<TranspositionTable>, // shared with the helper threads
+    threads: usize,
+    on_iteration: Option<InfoCallback>,
+    on_root_move: Option<CurrmoveCallback>,
+    tablebase: Arc<dyn Tablebase>,
+    tb_limit: u32, // probe at or below this many pieces
+    evaluator: E,
+    features: Features,
+}
+
+impl SearchState {
+    pub fn new() -> Self {
+        Self::with_evaluator(ClassicalEval)
+    }
+}
+
+impl<E: Evaluator> SearchState<E> {
+    /// Search state that scores positions with `evaluator`
+    pub fn with_evaluator(evaluator: E) -> Self {
+        Self::with_parts(evaluator, Arc::new(TranspositionTable::default()))
+    }
+
+    fn with_parts(evaluator: E, tt: Arc<TranspositionTable>) -> Self {
+        Self {
+            killers: [[None; 2]; MAX_PLY],
+            history: [[0; 64]; 64],
+            skip_null: false,
+            root_depth: 0,
+            completed_depth: 0,
+            seldepth: 0,
+            lines: Vec::new(),
+            root_excluded: Vec::new(),
+            nodes: 0,
+            stats: SearchStats::default(),
+            node_limit: None,
+            deadline: None,
+            clock_start: Instant::now(),
+            time_limits: (None, None),
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            pondering: false,
+            stopped: false,
+            tt,
+            threads: 1,
+            on_iteration: None,
+            on_root_move: None,
+            tablebase: Arc::new(NoTablebase),
+            tb_limit: 0,
+            evaluator,
+            features: Features::default(),
+        }
+    }
+
+    /// Forget everything learned from the previous game
+    pub fn new_game(&mut self) {
+        self.killers = [[None; 2]; MAX_PLY];
+        self.history = [[0; 64]; 64];
+        self.tt.clear();
+    }
+
+    /// Reallocate the transposition table to `mb` megabytes
+    pub fn set_hash_size(&mut self, mb: usize) {
+        self.tt = Arc::new(TranspositionTable::new(mb));
+    }
+
+    /// Empty the transposition table in place, keeping its size
+    pub fn clear_hash(&self) {
+        self.tt.clear();
+    }
+
+    /// Search with `threads` threads (lazy SMP): helpers run their own
+    /// iterative deepening over the shared transposition table
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.clamp(1, MAX_THREADS);
+    }
+
+    pub fn tt(&self) -> &TranspositionTable {
+        &self.tt
+    }
+
+    /// Shared flag that stops the search when set. It is not cleared by the
+    /// search itself, so reset it before starting a new one.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Consult `tablebase` at nodes with at most `limit` pieces (capped at what
+    /// the tables cover); a hit scores the node without searching below it
+    pub fn set_tablebase(&mut self, tablebase: Box<dyn Tablebase>, limit: u32) {
+        self.tb_limit = limit.min(tablebase.max_pieces());
+        self.tablebase = Arc::from(tablebase);
+    }
+
+    /// Shared flag marking the search as a ponder search: while set, the time
+    /// limits don't run. Clearing it (ponderhit) starts the clock.
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.ponder)
+    }
+
+    /// Call `callback` after every completed iteration of later searches
+    pub fn set_info_callback(&mut self, callback: impl FnMut(&SearchInfo) + Send + 'static) {
+        self.on_iteration = Some(Box::new(callback));
+    }
+
+    /// Call `callback` with the depth, move and 1-based move number as each root
+    /// move starts being searched, in iterations deep enough to take a while
+    pub fn set_currmove_callback(&mut self, callback: impl FnMut(u8, Move, usize) + Send + 'static) {
+        self.on_root_move = Some(Box::new(callback));
+    }
+
+    /// Depth of the last fully completed iteration
+    pub fn completed_depth(&self) -> u8 {
+        self.completed_depth
     }
 
-    let moves = generate_moves(board);
-    if moves.is_empty() {
-        // No legal moves: checkmate or stalemate
-        return if board.is_in_check(board.side_to_move()) {
-            -INF + 1 // Checkmate (add 1 to prefer shorter mates)
-        } else {
-            0 // Stalemate
+    /// Nodes visited by the current or last search
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Transposition table and move ordering counters of the current or last search
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// History score for a quiet move
+    pub fn history_score(&self, m: Move) -> i32 {
+        self.history[m.from as usize][m.to as usize]
+    }
+
+    /// Find best move at given depth, deepening one ply at a time
+    pub fn search(&mut self, board: &mut impl ChessBoard, depth: u8) -> (Move, i32) {
+        let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+        self.search_with_limits(board, &limits)
+    }
+
+    /// Iterative deepening until the depth or time limit is reached.
+    /// A timed-out iteration is discarded in favour of the last complete one.
+    pub fn search_with_limits(&mut self, board: &mut impl ChessBoard, limits: &SearchLimits) -> (Move, i32) {
+        let result = self.search_ex(board, limits);
+        (result.best_move, result.score)
+    }
+
+    /// `search_with_limits` with the full result
+    pub fn search_ex(&mut self, board: &mut impl ChessBoard, limits: &SearchLimits) -> SearchResult {
+        self.search_with_progress(board, limits, &mut |_| {})
+    }
+
+    /// `search_ex`, calling `progress` with the result so far after every
+    /// completed iteration. It is only ever called from the calling thread,
+    /// helper threads included.
+    pub fn search_with_progress<B: ChessBoard>(
+        &mut self,
+        board: &mut B,
+        limits: &SearchLimits,
+        progress: &mut dyn FnMut(&SearchResult),
+    ) -> SearchResult {
+        // Helpers search until this thread is done, then take the stop flag
+        let helper_stop = Arc::new(AtomicBool::new(false));
+        let helper_limits = SearchLimits { depth: limits.depth, multipv: limits.multipv, ..Default::default() };
+        let helpers: Vec<Self> = (1..self.threads).map(|_| self.helper(&helper_stop)).collect();
+        let helpers = thread::scope(|scope| {
+            let handles: Vec<_> = helpers
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut helper)| {
+                    let mut board = board.clone();
+                    let helper_limits = &helper_limits;
+                    // Every other helper starts a ply deeper so the threads spread over depths
+                    let first_depth = 1 + (i % 2 == 0) as u8;
+                    scope.spawn(move || {
+                        helper.iterate(&mut board, helper_limits, first_depth, &mut |_| {});
+                        helper
+                    })
+                })
+                .collect();
+            self.iterate(board, limits, 1, progress);
+            helper_stop.store(true, Ordering::Relaxed);
+            handles.into_iter().map(|h| h.join().expect("search thread panicked")).collect::<Vec<_>>()
+        });
+
+        // A helper that completed a deeper iteration has the better answer
+        for helper in helpers {
+            self.nodes += helper.nodes;
+            self.seldepth = self.seldepth.max(helper.seldepth);
+            if helper.completed_depth > self.completed_depth && !helper.lines.is_empty() {
+                self.completed_depth = helper.completed_depth;
+                self.lines = helper.lines;
+            }
+        }
+        self.result()
+    }
+
+    /// Result of the deepest completed iteration
+    fn result(&self) -> SearchResult {
+        let best = self.lines.first().expect("no legal moves");
+        SearchResult {
+            best_move: best.moves[0],
+            score: best.score,
+            depth: self.completed_depth,
+            seldepth: self.seldepth.min(u8::MAX as usize) as u8,
+            nodes: self.nodes,
+            pv: best.moves.clone(),
+            mate: mate_in(best.score),
+        }
+    }
+
+    /// Search state for a helper thread: shares the table and tablebase,
+    /// starts from this thread's history, and stops on `stop`
+    fn helper(&self, stop: &Arc<AtomicBool>) -> Self {
+        let mut helper = Self::with_parts(self.evaluator.clone(), Arc::clone(&self.tt));
+        helper.history = self.history;
+        helper.stop = Arc::clone(stop);
+        helper.tablebase = Arc::clone(&self.tablebase);
+        helper.tb_limit = self.tb_limit;
+        helper.features = self.features;
+        helper
+    }
+
+    /// Iterative deepening from `first_depth` on this thread
+    fn iterate(&mut self, board: &mut impl ChessBoard, limits: &SearchLimits, first_depth: u8, progress: &mut dyn FnMut(&SearchResult)) {
+        // Killers are position-specific; history carries over but decays
+        self.killers = [[None; 2]; MAX_PLY];
+        self.age_history();
+        self.nodes = 0;
+        self.stats = SearchStats::default();
+        self.node_limit = limits.nodes;
+        self.stopped = false;
+        self.completed_depth = 0;
+        let start = Instant::now();
+        self.time_limits = (limits.time, limits.movetime);
+        self.pondering = self.ponder.load(Ordering::Relaxed);
+        self.deadline = None;
+        if !self.pondering {
+            self.start_clock(start);
+        }
+
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let multipv = limits.multipv.max(1);
+        self.lines.clear();
+
+        for d in first_depth..=max_depth {
+            // Each pass finds the best root move not already reported this iteration
+            let mut lines: Vec<PvLine> = Vec::with_capacity(multipv);
+            self.seldepth = 0;
+            for k in 0..multipv {
+                self.root_excluded = lines.iter().map(|line| line.moves[0]).collect();
+                let prev = self.lines.get(k).cloned();
+                match self.aspiration_search(board, d, prev.as_ref()) {
+                    Some(line) => lines.push(line),
+                    None => break,
+                }
+            }
+            self.root_excluded.clear();
+            if self.stopped {
+                break;
+            }
+            lines.sort_by_key(|line| -line.score);
+            self.lines = lines;
+            self.completed_depth = d;
+            if let Some(callback) = self.on_iteration.as_mut() {
+                callback(&SearchInfo {
+                    depth: d,
+                    seldepth: self.seldepth,
+                    nodes: self.nodes,
+                    elapsed: start.elapsed(),
+                    hashfull: self.tt.hashfull(),
+                    lines: &self.lines,
+                });
+            }
+            progress(&self.result());
+
+            // Stop if out of time, or if the next iteration is unlikely to finish
+            if self.stop.load(Ordering::Relaxed) || self.node_limit.is_some_and(|n| self.nodes >= n) {
+                break;
+            }
+            self.check_ponderhit();
+            if let Some(budget) = limits.time {
+                if !self.pondering && self.clock_start.elapsed() > budget / 2 {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Best lines from the last completed iteration, best first
+    pub fn pv_lines(&self) -> &[PvLine] {
+        &self.lines
+    }
+
+    /// Search one root line at `depth` with a window around the previous
+    /// iteration's score. None if stopped or no root moves remain.
+    fn aspiration_search(&mut self, board: &mut impl ChessBoard, depth: u8, prev: Option<&PvLine>) -> Option<PvLine> {
+        let mut window = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match prev {
+            Some(line) if depth > 1 => (line.score - window, line.score + window),
+            _ => (-INF, INF),
         };
+        let prev_pv = prev.map_or(&[][..], |line| &line.moves[..]);
+
+        loop {
+            let mut line = Vec::new();
+            let score = self.search_root(board, depth, alpha, beta, prev_pv, &mut line);
+            if self.stopped || line.is_empty() {
+                return None;
+            }
+
+            if score <= alpha || score >= beta {
+                // Fell outside the window: widen and search this depth again
+                self.stats.aspiration_researches += 1;
+                window *= 2;
+                if window > ASPIRATION_MAX_WINDOW {
+                    alpha = -INF;
+                    beta = INF;
+                } else if score <= alpha {
+                    alpha = (score - window).max(-INF);
+                } else {
+                    beta = (score + window).min(INF);
+                }
+                continue;
+            }
+
+            return Some(PvLine { score, moves: line });
+        }
     }
 
-    for m in moves {
-        board.make_move(m);
-        let score = -negamax(board, depth - 1, -beta, -alpha);
-        board.unmake_move();
+    /// Run the time limits from `now`
+    fn start_clock(&mut self, now: Instant) {
+        let (budget, movetime) = self.time_limits;
+        self.clock_start = now;
+        self.deadline = [budget, movetime].into_iter().flatten().min().map(|t| now + t);
+    }
 
-        if score >= beta {
-            return beta; // Beta cutoff
+    /// A ponder search whose flag was cleared becomes a normal timed search
+    fn check_ponderhit(&mut self) {
+        if self.pondering && !self.ponder.load(Ordering::Relaxed) {
+            self.pondering = false;
+            self.start_clock(Instant::now());
         }
-        if score > alpha {
-            alpha = score;
+    }
+
+    /// Count a node and check the node limit, plus the clock and stop flag every
+    /// few thousand nodes. The first iteration is never interrupted so a move is
+    /// always available.
+    fn check_stop(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.stopped && self.root_depth > 1 {
+            self.stopped = self.node_limit.is_some_and(|n| self.nodes >= n);
+            if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+                self.check_ponderhit();
+                self.stopped |= self.stop.load(Ordering::Relaxed)
+                    || self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            }
         }
+        self.stopped
+    }
+
+    /// One root iteration; the previous iteration's best move is searched first
+    fn search_root(&mut self, board: &mut impl ChessBoard, depth: u8, mut alpha: i32, beta: i32, prev_pv: &[Move], pv: &mut Vec<Move>) -> i32 {
+        let mut moves = generate_legal(board);
+        moves.retain(|m| !self.root_excluded.contains(m));
+        self.order_moves(board, &mut moves, 0);
+        if let Some(&pv_move) = prev_pv.first() {
+            if let Some(idx) = moves.iter().position(|&m| m == pv_move) {
+                moves[..=idx].rotate_right(1);
+            }
+        }
+
+        self.root_depth = depth;
+        let mut best_score = -INF;
+        for (i, m) in moves.into_iter().enumerate() {
+            if depth >= CURRMOVE_MIN_DEPTH {
+                if let Some(callback) = self.on_root_move.as_mut() {
+                    callback(depth, m, i + 1);
+                }
+            }
+            let mut child_pv = Vec::new();
+            board.make_move(m);
+            let score = -self.negamax(board, depth - 1, 1, -beta, -alpha, &mut child_pv);
+            board.unmake_move();
+            if self.stopped {
+                break;
+            }
+
+            if score >= beta {
+                update_pv(pv, m, &child_pv);
+                return score;
+            }
+            if score > best_score || pv.is_empty() {
+                best_score = best_score.max(score);
+                alpha = alpha.max(score);
+                update_pv(pv, m, &child_pv);
+            }
+        }
+        best_score
+    }
+
+    /// Negamax with alpha-beta pruning
+    fn negamax(&mut self, board: &mut impl ChessBoard, mut depth: u8, ply: usize, mut alpha: i32, mut beta: i32, pv: &mut Vec<Move>) -> i32 {
+        let null_allowed = !std::mem::take(&mut self.skip_null);
+        if self.check_stop() {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply);
+        let us = board.side_to_move();
+        let in_check = board.is_in_check(us);
+
+        // Check extension: resolve checks a ply deeper. Bounded so a path is at
+        // most twice the nominal depth, which stops perpetual checks exploding.
+        if self.features.check_extension && in_check && ply < 2 * self.root_depth as usize {
+            depth += 1;
+        }
+
+        // Fifty-move rule, unless this very move delivered mate. The root never
+        // gets here, so claiming the draw stays a game-level decision.
+        if board.is_fifty_move_draw() && !(in_check && generate_legal(board).is_empty()) {
+            return 0;
+        }
+
+        // A repeated position is a draw: repeating it again is always possible
+        if board.is_repetition() {
+            return 0;
+        }
+
+        // Mate distance pruning: nothing below this node scores better than a
+        // mate at this ply or worse than being mated at it, so clamp the window
+        // to those bounds and give up if it closes (a shorter mate is known)
+        alpha = alpha.max(-INF + ply as i32);
+        beta = beta.min(INF - ply as i32);
+        if alpha >= beta {
+            return alpha;
+        }
+
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        if let Some(score) = self.probe_tablebase(board, ply) {
+            return score;
+        }
+
+        // Transposition table: reuse a result searched at least as deep
+        let key = board.zobrist_hash();
+        let tt_entry = self.tt.probe(key);
+        self.stats.tt_probes += 1;
+        self.stats.tt_hits += tt_entry.is_some() as u64;
+        if let Some(entry) = tt_entry {
+            if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        // Static eval for the pruning decisions near the leaves; meaningless in check
+        let static_eval = (!in_check && depth <= RFP_MAX_DEPTH.max(FUTILITY_MAX_DEPTH))
+            .then(|| self.evaluator.evaluate(board));
+
+        // Reverse futility pruning: near the leaves, a static eval far above
+        // beta is almost surely a cutoff. Kept off PV nodes and mate scores.
+        if let Some(eval) =
+            static_eval.filter(|_| self.features.rfp && depth <= RFP_MAX_DEPTH && beta - alpha == 1 && beta.abs() < TB_WIN)
+        {
+            let score = eval - RFP_MARGIN * depth as i32;
+            if score >= beta {
+                return score;
+            }
+        }
+
+        // Null-move pruning: if passing still beats beta, a real move will too.
+        // Skipped at PV nodes, whose exact score matters, in check, and with
+        // only pawns left, where zugzwang is likely.
+        if self.features.null_move
+            && null_allowed
+            && beta - alpha == 1
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && !in_check
+            && board.non_pawn_material(us) > 0
+        {
+            board.make_null_move();
+            self.skip_null = true;
+            let score = -self.negamax(board, depth - 1 - NULL_MOVE_R, ply + 1, -beta, -beta + 1, &mut Vec::new());
+            board.unmake_null_move();
+            if score >= beta {
+                // A mate found after passing isn't a real mate; don't report it
+                return if score >= INF - MATE_RANGE { beta } else { score };
+            }
+        }
+
+        // Fail-soft: the best score is returned even outside the window, so
+        // the TT bound and any aspiration re-search are as tight as possible
+        let original_alpha = alpha;
+        let mut best_score = -INF;
+        let mut best_move = None;
+        let mut child_pv = Vec::new();
+
+        // Futility pruning: at frontier nodes a quiet move can't be expected to
+        // gain more than the margin, so when that still misses alpha it's skipped
+        let futility_score = static_eval
+            .filter(|_| self.features.futility && depth <= FUTILITY_MAX_DEPTH && alpha.abs() < TB_WIN)
+            .map(|eval| eval + futility_margin(depth))
+            .filter(|&score| score <= alpha);
+
+        // Moves come lazily, best first, so a cutoff skips generating the rest
+        let killers = self.killers.get(ply).copied().unwrap_or([None; 2]);
+        let mut picker = MovePicker::new(tt_entry.and_then(|e| e.best_move), killers);
+        let mut moves_seen = 0;
+        let mut moves_searched = 0;
+        let mut quiets_tried = 0;
+        while let Some(m) = picker.next(board, &self.history) {
+            moves_seen += 1;
+            child_pv.clear();
+            let quiet = is_quiet(board, m);
+            board.make_move(m);
+
+            // Quiet moves that don't give check are skipped once futile, or
+            // once enough have been tried that the rest are unlikely to help
+            if quiet {
+                let late = self.features.lmp
+                    && !in_check
+                    && beta - alpha == 1
+                    && depth <= LMP_MAX_DEPTH
+                    && quiets_tried >= lmp_threshold(depth);
+                if (futility_score.is_some() || late) && !board.is_in_check(board.side_to_move()) {
+                    board.unmake_move();
+                    if let Some(score) = futility_score {
+                        best_score = best_score.max(score);
+                    }
+                    continue;
+                }
+                quiets_tried += 1;
+            }
+
+            // Pruned moves don't count: the first move searched gets the full
+            // window, and reductions grow with the moves actually searched
+            let i = moves_searched;
+            moves_searched += 1;
+
+            // Principal variation search: the first move gets the full window.
+            // The rest only have to be shown no better than it with a null
+            // window, and are searched again in full if they turn out better.
+            let mut score;
+            if i == 0 || !self.features.pvs {
+                score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            } else {
+                // Late move reduction: quiet moves late in the ordering are
+                // probably bad, so try them shallower first
+                let gives_check = board.is_in_check(board.side_to_move());
+                score = alpha + 1;
+                if self.features.lmr && quiet && !in_check && !gives_check && i >= LMR_MIN_INDEX && depth >= LMR_MIN_DEPTH {
+                    let r = lmr_reduction(depth, i);
+                    score = -self.negamax(board, depth - 1 - r, ply + 1, -alpha - 1, -alpha, &mut child_pv);
+                }
+                if score > alpha {
+                    child_pv.clear();
+                    score = -self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha, &mut child_pv);
+                }
+                if score > alpha && score < beta {
+                    child_pv.clear();
+                    score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+                }
+            }
+            board.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                self.stats.cutoffs += 1;
+                self.stats.first_move_cutoffs += (i == 0) as u64;
+                if quiet {
+                    self.store_killer(ply, m);
+                    self.update_history(m, depth);
+                }
+                self.tt.store(key, Some(m), score_to_tt(score, ply), depth, Bound::Lower);
+                return score; // Beta cutoff
+            }
+            best_score = best_score.max(score);
+            if score > alpha {
+                alpha = score;
+                best_move = Some(m);
+                update_pv(pv, m, &child_pv);
+            }
+        }
+
+        if moves_seen == 0 {
+            // No legal moves: checkmate or stalemate
+            return if in_check {
+                -INF + ply as i32 // Checkmate, scored by distance so shorter mates are preferred
+            } else {
+                0 // Stalemate
+            };
+        }
+
+        let bound = if alpha > original_alpha { Bound::Exact } else { Bound::Upper };
+        self.tt.store(key, best_move, score_to_tt(best_score, ply), depth, bound);
+        best_score
+    }
+
+    /// Tablebase score for a position with few enough pieces. Cursed wins and
+    /// blessed losses are draws under the fifty-move rule.
+    fn probe_tablebase(&self, board: &impl ChessBoard, ply: usize) -> Option<i32> {
+        let pieces = (board.occupancy(Color::White) | board.occupancy(Color::Black)).count_ones();
+        if pieces > self.tb_limit || board.castling_rights() != 0 {
+            return None;
+        }
+        let score = match self.tablebase.probe_wdl(&Board::from_position(board))? {
+            Wdl::Win => TB_WIN - ply as i32,
+            Wdl::Loss => -TB_WIN + ply as i32,
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => 0,
+        };
+        Some(score)
+    }
+
+    /// Quiescence search: keep resolving captures until the position is quiet,
+    /// so the static eval is never taken in the middle of an exchange
+    fn quiescence(&mut self, board: &mut impl ChessBoard, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.check_stop() {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply);
+
+        let stand_pat = self.evaluator.evaluate(board);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+        let mut best_score = stand_pat;
+
+        let mut moves = MoveList::new();
+        generate_captures_into(board, GenMode::NoUnderpromotion, &mut moves);
+        order_moves(board, &mut moves);
+        let delta_pruning = game_phase(board) > DELTA_MIN_PHASE;
+
+        for &m in &moves {
+            if delta_pruning && m.promotion().is_none() {
+                let captured = if m.is_en_passant() {
+                    Piece::Pawn
+                } else {
+                    board.piece_at(m.to).map_or(Piece::Pawn, |(p, _)| p)
+                };
+                if stand_pat + PIECE_VALUES[captured as usize] + DELTA_MARGIN < alpha {
+                    continue;
+                }
+            }
+
+            board.make_move(m);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move();
+
+            if score >= beta {
+                return score;
+            }
+            best_score = best_score.max(score);
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        best_score
+    }
+
+    /// Sort moves best-first: captures by MVV-LVA, then killers, then quiet moves by history
+    fn order_moves(&self, board: &impl ChessBoard, moves: &mut [Move], ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or([None; 2]);
+        moves.sort_by_cached_key(|&m| {
+            let score = score_move(board, m);
+            if score == 0 {
+                match killers.iter().position(|&k| k == Some(m)) {
+                    Some(slot) => -KILLER_SCORES[slot],
+                    None => -self.history_score(m),
+                }
+            } else {
+                -score
+            }
+        });
+    }
+
+    /// Remember a quiet move that caused a beta cutoff at this ply
+    fn store_killer(&mut self, ply: usize, m: Move) {
+        if let Some(slot) = self.killers.get_mut(ply) {
+            if slot[0] != Some(m) {
+                slot[1] = slot[0];
+                slot[0] = Some(m);
+            }
+        }
+    }
+
+    /// Reward a quiet cutoff move, weighted by the remaining depth
+    fn update_history(&mut self, m: Move, depth: u8) {
+        let entry = &mut self.history[m.from as usize][m.to as usize];
+        *entry += depth as i32 * depth as i32;
+        if *entry >= HISTORY_MAX {
+            self.age_history();
+        }
+    }
+
+    /// Halve every history score so old information fades
+    fn age_history(&mut self) {
+        for row in self.history.iter_mut() {
+            for entry in row.iter_mut() {
+                *entry /= 2;
+            }
+        }
+    }
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self::new()
     }
-    alpha
 }
 
-// Phase 2 improvements:
-// - Iterative deepening
-// - Move ordering (captures first, killer moves, history heuristic)
-// - Transposition table
-// - Quiescence search
-// - Check extensions
+/// Sort moves best-first: captures by MVV-LVA, then quiet moves
+pub fn order_moves(board: &impl ChessBoard, moves: &mut [Move]) {
+    moves.sort_by_cached_key(|&m| -score_move(board, m));
+}
+
+/// Most Valuable Victim - Least Valuable Attacker score for captures, 0 for quiet moves
+pub(crate) fn score_move(board: &impl ChessBoard, m: Move) -> i32 {
+    if !board.is_capture(m) {
+        return 0;
+    }
+    let victim = if m.is_en_passant() {
+        Piece::Pawn
+    } else {
+        board.piece_at(m.to).map_or(Piece::Pawn, |(p, _)| p)
+    };
+    let attacker = board.piece_at(m.from).map_or(Piece::Pawn, |(p, _)| p);
+    MVV_LVA_BASE + 10 * PIECE_VALUES[victim as usize] - PIECE_VALUES[attacker as usize]
+}
+
+/// Static exchange evaluation: net material (centipawns) the side to move
+/// expects from `m` if both sides keep recapturing on the target square with
+/// their least valuable attacker, each free to stop when that is better
+pub fn see(board: &impl ChessBoard, m: Move) -> i32 {
+    let value = |piece: Piece| PIECE_VALUES[piece as usize];
+    let (mut on_square, _) = board.piece_at(m.from).expect("see: no piece on from square");
+    let mut occupied = (board.occupancy(Color::White) | board.occupancy(Color::Black)) ^ (1u64 << m.from);
+
+    // gain[d]: material won by the capture at depth d, if it went unanswered
+    let mut gain = [0; 32];
+    if m.is_en_passant() {
+        // The captured pawn sits beside the target, on the mover's rank
+        occupied ^= 1u64 << (m.to ^ 8);
+        gain[0] = value(Piece::Pawn);
+    } else if let Some((captured, _)) = board.piece_at(m.to) {
+        gain[0] = value(captured);
+    }
+    if let Some(promo) = m.promotion() {
+        gain[0] += value(promo) - value(Piece::Pawn);
+        on_square = promo;
+    }
+
+    let promotion_rank = matches!(rank_of(m.to), 0 | 7);
+    let mut side = board.side_to_move().opposite();
+    let mut depth = 0;
+    while depth + 1 < gain.len() {
+        let attackers = board.attackers_through(m.to, side, occupied) & occupied;
+        let Some((piece, from)) = least_valuable_attacker(board, attackers, side) else {
+            break;
+        };
+        // A king may only recapture if nothing can take it back
+        if piece == Piece::King
+            && board.attackers_through(m.to, side.opposite(), occupied ^ (1u64 << from)) & occupied != 0
+        {
+            break;
+        }
+
+        depth += 1;
+        gain[depth] = value(on_square) - gain[depth - 1];
+        on_square = piece;
+        if piece == Piece::Pawn && promotion_rank {
+            gain[depth] += value(Piece::Queen) - value(Piece::Pawn);
+            on_square = Piece::Queen;
+        }
+        occupied ^= 1u64 << from;
+        side = side.opposite();
+    }
+
+    // Unwind: each side only recaptures if it doesn't lose by doing so
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+    gain[0]
+}
+
+/// Cheapest piece among `attackers` and the square it stands on
+fn least_valuable_attacker(board: &impl ChessBoard, attackers: Bitboard, side: Color) -> Option<(Piece, Square)> {
+    [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+        .into_iter()
+        .find_map(|piece| {
+            let bb = attackers & board.pieces(side, piece);
+            (bb != 0).then(|| (piece, bb.trailing_zeros() as Square))
+        })
+}
+
+/// Moves until mate for a mate score, negative when being mated; None for
+/// ordinary scores
+pub fn mate_in(score: i32) -> Option<i32> {
+    let plies = INF - score.abs();
+    if plies > MATE_RANGE {
+        return None;
+    }
+    let moves = (plies + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
+
+/// Mate scores count plies from the root, but a TT entry can be reached at any
+/// ply; store them relative to the node instead
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > INF - MATE_RANGE {
+        score + ply as i32
+    } else if score < -INF + MATE_RANGE {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Inverse of `score_to_tt` for a node at `ply`
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > INF - MATE_RANGE {
+        score - ply as i32
+    } else if score < -INF + MATE_RANGE {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Largest gain expected from a quiet move `depth` plies from the horizon
+fn futility_margin(depth: u8) -> i32 {
+    FUTILITY_MARGIN * depth as i32
+}
+
+/// Quiet moves tried at `depth` before late move pruning starts
+fn lmp_threshold(depth: u8) -> usize {
+    3 + depth as usize * depth as usize
+}
+
+/// Late move reduction in plies, growing with depth and move index.
+/// Never reduces below depth 1 so the reduced search still makes a move.
+pub fn lmr_reduction(depth: u8, move_index: usize) -> u8 {
+    let r = 0.75 + (depth as f32).ln() * (move_index as f32).ln() / 2.25;
+    (r as u8).clamp(1, depth.saturating_sub(2).max(1))
+}
+
+/// Quiet moves are neither captures nor promotions
+fn is_quiet(board: &impl ChessBoard, m: Move) -> bool {
+    !board.is_capture(m) && m.promotion().is_none()
+}
+
+/// Principal variation becomes `m` followed by the child's line
+fn update_pv(pv: &mut Vec<Move>, m: Move, child_pv: &[Move]) {
+    pv.clear();
+    pv.push(m);
+    pv.extend_from_slice(child_pv);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::eval::evaluate;
+    use crate::movegen::generate_moves;
+
+    fn fen(fen: &str) -> Board {
+        Board::try_from_fen(fen).expect("valid test FEN")
+    }
+
+    fn uci(board: &Board, s: &str) -> Move {
+        generate_legal(&mut board.clone())
+            .into_iter()
+            .find(|m| m.to_string() == s)
+            .expect("legal test move")
+    }
+
+    #[test]
+    fn quiescence_sees_defended_queen_capture() {
+        // Qxd5 wins a knight but the e6 pawn takes the queen back
+        let mut board = fen("4k3/7p/4p3/3n4/8/8/7P/3QK3 w - - 0 1");
+        let static_eval = evaluate(&board);
+        let mut state = SearchState::new();
+        let score = state.quiescence(&mut board, 0, -INF, INF);
+        assert!(score < static_eval + 100, "quiescence {} thinks a capture wins", score);
+
+        let (best, score) = state.search(&mut board, 1);
+        assert_ne!(best, uci(&board, "d1d5"));
+        assert!(score < static_eval + 100, "depth 1 scores {}", score);
+    }
+
+    #[test]
+    fn iterative_deepening_matches_direct_search() {
+        // Nc7+ forks the king and the a8 rook
+        let mut board = fen("r3k3/pp3ppp/8/1N6/8/8/PP3PPP/4K3 w - - 0 1");
+        let (deepened, _) = SearchState::new().search(&mut board, 4);
+
+        let mut direct = SearchState::new();
+        let mut pv = Vec::new();
+        direct.search_root(&mut board, 4, -INF, INF, &[], &mut pv);
+        assert_eq!(deepened, pv[0]);
+        assert_eq!(deepened, uci(&board, "b5c7"));
+    }
+
+    #[test]
+    fn order_moves_puts_valuable_captures_first() {
+        let board = fen("3qk3/8/8/8/8/2p5/1P2P3/3QK3 w - - 0 1");
+        let mut moves = generate_moves(&board);
+        order_moves(&board, &mut moves);
+        let index = |s: &str| moves.iter().position(|&m| m == uci(&board, s)).expect("move generated");
+        assert_eq!(index("d1d8"), 0);
+        assert_eq!(index("b2c3"), 1);
+        for push in ["e2e3", "e2e4", "b2b3", "b2b4"] {
+            assert!(index("b2c3") < index(push));
+        }
+
+        // En passant scores as pawn takes pawn
+        let board = fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let ep = uci(&board, "e5d6");
+        assert!(ep.is_en_passant());
+        assert_eq!(score_move(&board, ep), MVV_LVA_BASE + 9 * PIECE_VALUES[Piece::Pawn as usize]);
+    }
+
+    #[test]
+    fn killer_is_tried_early_in_sibling() {
+        let mut state = SearchState::new();
+        let mut sibling = Board::new();
+        sibling.make_move(uci(&sibling, "d2d4"));
+        let killer = uci(&sibling, "g8f6");
+        let mut moves = generate_moves(&sibling);
+        state.order_moves(&sibling, &mut moves, 1);
+        assert_ne!(moves[0], killer);
+
+        // A cutoff by Nf6 after 1.e4 makes it the first try after 1.d4
+        let mut board = Board::new();
+        board.make_move(uci(&board, "e2e4"));
+        state.store_killer(1, uci(&board, "g8f6"));
+        state.order_moves(&sibling, &mut moves, 1);
+        assert_eq!(moves[0], killer);
+        let mut picker = MovePicker::new(None, state.killers[1]);
+        assert_eq!(picker.next(&mut sibling, &state.history), Some(killer));
+
+        // The search records quiet cutoff moves itself
+        let mut state = SearchState::new();
+        state.search(&mut Board::new(), 4);
+        assert!(state.killers.iter().any(|slot| slot[0].is_some()));
+    }
+
+    #[test]
+    fn history_grows_and_orders_quiet_moves() {
+        let mut state = SearchState::new();
+        let board = Board::new();
+        let (nf3, c4) = (uci(&board, "g1f3"), uci(&board, "c2c4"));
+        state.update_history(nf3, 3);
+        let after_one = state.history_score(nf3);
+        state.update_history(nf3, 3);
+        state.update_history(c4, 2);
+        assert!(state.history_score(nf3) > after_one);
+        assert!(state.history_score(nf3) > state.history_score(c4));
+
+        let mut moves = generate_moves(&board);
+        state.order_moves(&board, &mut moves, 0);
+        assert_eq!(&moves[..2], &[nf3, c4]);
+
+        // A real search rewards its cutoff moves; a new game forgets them
+        let mut state = SearchState::new();
+        let mut board = Board::new();
+        for s in ["e2e4", "e7e5", "g1f3"] {
+            board.make_move(uci(&board, s));
+        }
+        state.search(&mut board, 4);
+        assert!(state.history.iter().flatten().any(|&score| score > 0));
+        state.new_game();
+        assert!(state.history.iter().flatten().all(|&score| score == 0));
+    }
+
+    /// Search `position` to `depth` with every feature on and again with
+    /// `disable` applied: the move must match and the features must save nodes
+    fn assert_pruning_saves_nodes(position: &str, depth: u8, disable: impl Fn(&mut Features)) {
+        let mut with = SearchState::new();
+        let (best, _) = with.search(&mut fen(position), depth);
+        let mut without = SearchState::new();
+        disable(&mut without.features);
+        let (best_without, _) = without.search(&mut fen(position), depth);
+        assert_eq!(best, best_without);
+        assert!(with.nodes() < without.nodes(), "{} nodes with the feature, {} without", with.nodes(), without.nodes());
+    }
+
+    #[test]
+    fn null_move_saves_nodes_without_changing_the_move() {
+        assert_pruning_saves_nodes("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 8", 6, |f| f.null_move = false);
+    }
+
+    #[test]
+    fn null_move_never_hides_a_mate() {
+        // WAC.001: Qg6 mates in two, which every depth must keep seeing
+        let position = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1";
+        for depth in 3..=7 {
+            let result = SearchState::new().search_ex(&mut fen(position), &SearchLimits { depth: Some(depth), ..Default::default() });
+            assert_eq!(result.mate, Some(2), "depth {}", depth);
+            assert_eq!(result.best_move, uci(&fen(position), "g3g6"), "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn check_extension_finds_mate_sooner() {
+        // Mate in 3 by checks. Unextended, it takes depth 6: five plies of
+        // moves and one more to find the mated side without a reply.
+        let position = "r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1";
+        let limits = SearchLimits { depth: Some(3), ..Default::default() };
+        let extended = SearchState::new().search_ex(&mut fen(position), &limits);
+        assert_eq!(extended.mate, Some(3));
+
+        let mut state = SearchState::new();
+        state.features.check_extension = false;
+        assert_eq!(state.search_ex(&mut fen(position), &limits).mate, None);
+        for depth in 4..=5 {
+            let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+            assert_eq!(state.search_ex(&mut fen(position), &limits).mate, None);
+        }
+        let limits = SearchLimits { depth: Some(6), ..Default::default() };
+        assert_eq!(state.search_ex(&mut fen(position), &limits).mate, Some(3));
+    }
+
+    #[test]
+    fn lmr_reduction_grows_but_leaves_a_ply() {
+        for depth in LMR_MIN_DEPTH..=20 {
+            let mut previous = 0;
+            for index in LMR_MIN_INDEX..64 {
+                let r = lmr_reduction(depth, index);
+                assert!(r >= 1 && r < depth, "depth {} index {} reduces {}", depth, index, r);
+                assert!(r >= previous);
+                previous = r;
+            }
+        }
+        assert!(lmr_reduction(12, 40) > lmr_reduction(3, 4));
+    }
+
+    #[test]
+    fn lmr_saves_nodes_without_changing_the_move() {
+        assert_pruning_saves_nodes("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 8", 6, |f| f.lmr = false);
+    }
+
+    #[test]
+    fn stable_position_needs_no_aspiration_research() {
+        let mut state = SearchState::new();
+        state.search(&mut Board::new(), 5);
+        assert_eq!(state.stats().aspiration_researches, 0);
+    }
+
+    #[test]
+    fn swingy_position_researches_and_keeps_the_move() {
+        // WAC.001: the score jumps to a mate once Qg6 is seen through
+        let mut board = fen("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        let expected = uci(&board, "g3g6");
+        let mut state = SearchState::new();
+        let (best, score) = state.search(&mut board, 5);
+        assert!(state.stats().aspiration_researches > 0);
+        assert_eq!(best, expected);
+        assert_eq!(mate_in(score), Some(2));
+    }
+
+    #[test]
+    fn raised_stop_flag_still_returns_a_legal_move() {
+        let mut board = fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        let mut state = SearchState::new();
+        state.stop_flag().store(true, Ordering::Relaxed);
+        let result = state.search_ex(&mut board, &SearchLimits::default());
+        assert!(result.depth <= 1);
+        assert!(board.legal_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn multipv_reports_distinct_moves_best_first() {
+        let mut state = SearchState::new();
+        let limits = SearchLimits { depth: Some(4), multipv: 2, ..Default::default() };
+        let result = state.search_ex(&mut Board::new(), &limits);
+        let lines = state.pv_lines();
+        assert_eq!(lines.len(), 2);
+        assert_ne!(lines[0].moves[0], lines[1].moves[0]);
+        assert!(lines[0].score >= lines[1].score);
+        assert_eq!(result.best_move, lines[0].moves[0]);
+
+        // Only one legal move: only one line to report
+        let mut board = fen("k7/8/8/8/8/8/1r6/K7 w - - 0 1");
+        let limits = SearchLimits { depth: Some(3), multipv: 3, ..Default::default() };
+        state.search_ex(&mut board, &limits);
+        assert_eq!(state.pv_lines().len(), 1);
+    }
+
+    #[test]
+    fn node_limit_stops_the_search() {
+        let mut board = fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 8");
+        for limit in [2_000, 20_000] {
+            let limits = SearchLimits { nodes: Some(limit), ..Default::default() };
+            let result = SearchState::new().search_ex(&mut board, &limits);
+            assert!(result.nodes >= limit / 2 && result.nodes <= limit * 2, "{} nodes for a limit of {}", result.nodes, limit);
+            assert!(board.legal_moves().contains(&result.best_move));
+        }
+    }
+
+    #[test]
+    fn see_scores_exchanges() {
+        let see_of = |position: &str, m: &str| {
+            let board = fen(position);
+            see(&board, uci(&board, m))
+        };
+        // Queen takes a pawn defended by a pawn
+        assert_eq!(see_of("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), 100 - 900);
+        // Undefended rook
+        assert_eq!(see_of("4k3/8/8/3r4/8/4N3/8/4K3 w - - 0 1", "e3d5"), 500);
+        // Bxd5 exd5 Rxd5: knight and pawn for the bishop
+        assert_eq!(see_of("6k1/8/4p3/3n4/8/1B6/8/3R2K1 w - - 0 1", "b3d5"), 320 - 330 + 100);
+        // Black won't recapture with the rook when the bishop behind would win it
+        assert_eq!(see_of("3r2k1/8/8/3p4/4P3/1B6/8/6K1 w - - 0 1", "e4d5"), 100);
+        // En passant takes a pawn that isn't on the target square
+        assert_eq!(see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        // Promoting on the capture square adds the queen less the pawn
+        assert_eq!(see_of("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7a8q"), 500 + 900 - 100);
+    }
+
+    #[test]
+    fn delta_pruning_skips_only_hopeless_captures() {
+        // Nodes quiescence visits when it needs 500 cp more than the static eval
+        let visited = |position: &str| {
+            let mut board = fen(position);
+            let mut state = SearchState::new();
+            let alpha = evaluate(&board) + 500;
+            state.quiescence(&mut board, 0, alpha, alpha + 1);
+            state.nodes
+        };
+        // Qxd5 wins a pawn: even with the margin that can't reach alpha
+        assert_eq!(visited("4k2r/8/8/3p4/8/8/8/R2QK3 w - - 0 1"), 1);
+        // Qxd5 wins a queen, which might
+        assert!(visited("4k2r/8/8/3q4/8/8/8/R2QK3 w - - 0 1") > 1);
+        // With little material left nothing is pruned
+        assert!(visited("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1") > 1);
+    }
+
+    #[test]
+    fn fifty_move_rule_scores_a_draw() {
+        let score_after = |position: &str, moves: &[&str]| {
+            let mut board = fen(position);
+            for m in moves {
+                let m = uci(&board, m);
+                board.make_move(m);
+            }
+            let mut state = SearchState::new();
+            let score = state.negamax(&mut board, 2, moves.len(), -INF, INF, &mut Vec::new());
+            (board.is_fifty_move_draw(), score)
+        };
+
+        // A queen up, but four more shuffling moves reach the hundredth halfmove
+        let shuffle = ["d1d2", "e8f7", "d2d3", "f7g7"];
+        let (draw, score) = score_after("4k3/8/8/8/8/8/8/3QK3 w - - 96 100", &shuffle);
+        assert!(draw);
+        assert_eq!(score, 0);
+        let (draw, score) = score_after("4k3/8/8/8/8/8/8/3QK3 w - - 0 100", &shuffle);
+        assert!(!draw);
+        assert!(score > 500);
+
+        // Mate on the hundredth halfmove still counts as mate
+        let (draw, score) = score_after("7k/8/6K1/8/8/8/8/Q7 w - - 99 80", &["a1a8"]);
+        assert!(draw);
+        assert!(score <= -(INF - MATE_RANGE), "mated side scores {}", score);
+    }
+
+    #[test]
+    fn perpetual_check_scores_a_draw() {
+        // Two rooks down, but Qe8+ Kh7 Qh5+ Kg8 repeats for ever
+        let mut board = fen("6k1/6p1/8/7Q/8/8/qrr3PP/7K w - - 0 1");
+        assert!(evaluate(&board) < -500);
+        for depth in [4, 7] {
+            let (best, score) = SearchState::new().search(&mut board, depth);
+            assert_eq!(best, uci(&board, "h5e8"));
+            assert_eq!(score, 0, "depth {}", depth);
+        }
+        // Black can't escape the checks either
+        let mut board = fen("4Q1k1/6p1/8/8/8/8/qrr3PP/7K b - - 1 1");
+        assert_eq!(SearchState::new().search(&mut board, 6).1, 0);
+    }
+
+    /// Gives the same verdict for every position it is asked about
+    struct MockTablebase(Wdl);
+
+    impl Tablebase for MockTablebase {
+        fn max_pieces(&self) -> u32 {
+            5
+        }
+
+        fn probe_wdl(&self, _board: &Board) -> Option<Wdl> {
+            Some(self.0)
+        }
+
+        fn probe_dtz(&self, _board: &Board) -> Option<i32> {
+            None
+        }
+    }
+
+    #[test]
+    fn tablebase_verdict_is_used_at_the_threshold() {
+        // K+Q v K: three pieces, no mate in one
+        let board = fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        let search = |verdict, limit| {
+            let mut state = SearchState::new();
+            state.set_tablebase(Box::new(MockTablebase(verdict)), limit);
+            state.search(&mut board.clone(), 3).1
+        };
+        // Every reply is probed, and black's loss is white's win
+        assert_eq!(search(Wdl::Loss, 3), TB_WIN - 1);
+        assert_eq!(search(Wdl::Draw, 3), 0);
+        assert_eq!(search(Wdl::CursedWin, 3), 0);
+        // Above the limit the tables are never asked
+        let score = search(Wdl::Draw, 2);
+        assert!(score > 500 && score < TB_WIN - MAX_PLY as i32, "score {}", score);
+    }
+
+    /// Material only, counting how often it is called
+    #[derive(Clone, Default)]
+    struct MaterialEval(Arc<std::sync::atomic::AtomicU64>);
+
+    impl Evaluator for MaterialEval {
+        fn evaluate(&self, board: &impl ChessBoard) -> i32 {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            let us = board.side_to_move();
+            let material = |color| {
+                [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+                    .into_iter()
+                    .map(|p| PIECE_VALUES[p as usize] * board.pieces(color, p).count_ones() as i32)
+                    .sum::<i32>()
+            };
+            material(us) - material(us.opposite())
+        }
+    }
+
+    #[test]
+    fn search_scores_with_a_custom_evaluator() {
+        let eval = MaterialEval::default();
+        let mut state = SearchState::with_evaluator(eval.clone());
+        let (_, score) = state.search(&mut Board::new(), 3);
+        assert_eq!(score, 0);
+        assert!(eval.0.load(Ordering::Relaxed) > 0);
+
+        // The classical eval sees more than material in the same position
+        let (_, score) = SearchState::new().search(&mut Board::new(), 3);
+        assert_ne!(score, 0);
+
+        // Hanging queen: material alone is enough to take it
+        let mut board = fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1");
+        let (best, score) = SearchState::with_evaluator(MaterialEval::default()).search(&mut board, 2);
+        assert_eq!(best, uci(&board, "d1d5"));
+        assert_eq!(score, PIECE_VALUES[Piece::Rook as usize]);
+    }
+
+    #[test]
+    fn kqk_drives_the_king_to_the_edge() {
+        // Chebyshev distance of the lone king from the center: 3 on the edge
+        let from_center = |board: &Board| {
+            let king = board.pieces(Color::Black, Piece::King).trailing_zeros() as Square;
+            let axis = |x: u8| if x < 4 { 3 - x } else { x - 4 };
+            axis(file_of(king)).max(axis(rank_of(king)))
+        };
+        let mut board = fen("8/8/8/3k4/8/8/8/4K2Q w - - 0 1");
+        let mut distances = vec![from_center(&board)];
+        for _ in 0..20 {
+            let m = SearchState::new().search(&mut board, 4).0;
+            board.make_move(m);
+            if board.is_checkmate() {
+                break;
+            }
+            let m = SearchState::new().search(&mut board, 4).0;
+            board.make_move(m);
+            distances.push(from_center(&board));
+        }
+        assert!(distances.windows(2).all(|w| w[0] <= w[1]), "king escaped: {:?}", distances);
+        assert!(distances.iter().position(|&d| d == 3).is_some_and(|n| n <= 8), "{:?}", distances);
+        assert!(board.is_checkmate(), "no mate: {}", board.to_fen());
+    }
+
+    #[test]
+    fn two_threads_search_and_stop() {
+        let board = fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let mut state = SearchState::new();
+        state.set_threads(2);
+        let (best, _) = state.search(&mut board.clone(), 5);
+        assert!(generate_legal(&mut board.clone()).contains(&best));
+
+        // Unlimited search: only the stop flag ends it
+        let stop = state.stop_flag();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut search_board = board.clone();
+        thread::spawn(move || {
+            let result = state.search_ex(&mut search_board, &SearchLimits::default());
+            sender.send(result.best_move).expect("test receiver alive");
+        });
+        thread::sleep(Duration::from_millis(100));
+        stop.store(true, Ordering::Relaxed);
+        let best = receiver.recv_timeout(Duration::from_secs(10)).expect("search didn't stop");
+        assert!(generate_legal(&mut board.clone()).contains(&best));
+    }
+
+    #[test]
+    fn seldepth_reaches_past_the_nominal_depth() {
+        // Plenty of captures for quiescence to follow
+        let mut board = fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let result = SearchState::new().search_ex(&mut board, &SearchLimits { depth: Some(4), ..Default::default() });
+        assert_eq!(result.depth, 4);
+        assert!(result.seldepth > result.depth, "seldepth {}", result.seldepth);
+    }
+
+    #[test]
+    fn search_result_has_the_full_statistics() {
+        let mut board = fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4");
+        let limits = SearchLimits { depth: Some(5), ..Default::default() };
+        let result = SearchState::new().search_ex(&mut board, &limits);
+        assert_eq!(result.depth, 5);
+        assert!(result.nodes > 0);
+        assert_eq!(result.pv[0], result.best_move);
+        // Qxf7# is found and reported as mate in one
+        assert_eq!(result.best_move, uci(&board, "h5f7"));
+        assert_eq!(result.mate, Some(1));
+        assert_eq!(SearchState::new().search(&mut board, 5), (result.best_move, result.score));
+
+        let mut board = Board::new();
+        let result = SearchState::new().search_ex(&mut board, &SearchLimits { depth: Some(3), ..Default::default() });
+        assert_eq!(result.depth, 3);
+        assert!(result.nodes > 0 && result.seldepth >= result.depth);
+        assert_eq!(result.pv[0], result.best_move);
+        assert_eq!(result.mate, None);
+    }
+
+    #[test]
+    fn progress_is_reported_once_per_depth() {
+        for threads in [1, 2] {
+            let mut state = SearchState::new();
+            state.set_threads(threads);
+            let mut depths = Vec::new();
+            let limits = SearchLimits { depth: Some(6), ..Default::default() };
+            let result = state.search_with_progress(&mut Board::new(), &limits, &mut |r| depths.push(r.depth));
+            assert_eq!(depths, (1..=6).collect::<Vec<u8>>(), "{} threads", threads);
+            assert_eq!(result.depth, 6);
+        }
+    }
+
+    #[test]
+    fn pvs_saves_nodes_without_changing_the_result() {
+        let board = fen("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10");
+        let search = |pvs| {
+            let mut state = SearchState::new();
+            state.features.pvs = pvs;
+            // Null move and LMR are only tried in null windows; keep them out of the comparison
+            state.features.null_move = false;
+            state.features.lmr = false;
+            let result = state.search(&mut board.clone(), 5);
+            (result, state.nodes)
+        };
+        let (with, with_nodes) = search(true);
+        let (without, without_nodes) = search(false);
+        assert_eq!(with, without);
+        assert!(with_nodes < without_nodes, "pvs {} vs plain {}", with_nodes, without_nodes);
+    }
+
+    #[test]
+    fn fail_soft_returns_more_than_beta() {
+        // Rxd5 wins the queen: far more than the window asks for
+        let mut board = fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1");
+        let (alpha, beta) = (-50, 50);
+        let score = SearchState::new().negamax(&mut board, 3, 0, alpha, beta, &mut Vec::new());
+
+        // A fail-hard search would have returned beta; fail-soft gives a
+        // tighter lower bound, and the root move is the same either way
+        let (best, full) = SearchState::new().search(&mut board, 3);
+        assert!(score > beta && score <= full, "cut node returned {}, true score {}", score, full);
+        assert_eq!(best, uci(&board, "d1d5"));
+    }
+
+    #[test]
+    fn reverse_futility_prunes_only_out_of_check() {
+        // A queen up in a quiet position: far above a beta of 0 at depth 2
+        let mut board = fen("4k3/ppp5/8/8/8/8/PPP5/3QK3 w - - 0 1");
+        let mut state = SearchState::new();
+        let score = state.negamax(&mut board, 2, 1, -1, 0, &mut Vec::new());
+        assert_eq!(score, evaluate(&board) - 2 * RFP_MARGIN);
+        assert_eq!(state.nodes, 1);
+
+        let mut state = SearchState::new();
+        state.features.rfp = false;
+        state.negamax(&mut board, 2, 1, -1, 0, &mut Vec::new());
+        assert!(state.nodes > 1);
+
+        // Still well ahead, but in check: nothing is pruned
+        let mut board = fen("4k3/ppp5/8/8/8/8/PPP1r3/3QK3 w - - 0 1");
+        let mut state = SearchState::new();
+        state.negamax(&mut board, 2, 1, -1, 0, &mut Vec::new());
+        assert!(state.nodes > 1);
+    }
+
+    #[test]
+    fn futility_saves_nodes_without_changing_the_move() {
+        assert_pruning_saves_nodes("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8", 6, |f| f.futility = false);
+    }
+
+    #[test]
+    fn late_move_pruning_saves_nodes_without_changing_the_move() {
+        assert_pruning_saves_nodes("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", 7, |f| f.lmp = false);
+    }
+
+    #[test]
+    fn mate_in_three_is_stable_across_depths() {
+        let position = "r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1";
+        let mut state = SearchState::new();
+        let first = state.search_ex(&mut fen(position), &SearchLimits { depth: Some(3), ..Default::default() });
+        assert_eq!(first.mate, Some(3));
+        // Mated five plies from the root
+        assert_eq!(first.score, INF - 5);
+        assert_eq!(first.pv.len(), 5);
+        for depth in 4..=8 {
+            // Reusing the state keeps the TT, whose mate scores must not drift
+            let result = state.search_ex(&mut fen(position), &SearchLimits { depth: Some(depth), ..Default::default() });
+            assert_eq!(result.mate, Some(3), "depth {}", depth);
+            assert_eq!(result.score, first.score, "depth {}", depth);
+            assert_eq!(result.best_move, first.best_move, "depth {}", depth);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// === Endgame Tablebases ===
+// Module owner: @i3mjagsb
+
+use crate::board::Board;
+
+/// Win/draw/loss verdict from the side to move's point of view. Cursed wins and
+/// blessed losses are decided positions that the fifty-move rule turns into draws.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+/// Endgame tablebase prober (e.g. Syzygy), plugged into the search with
+/// `SearchState::set_tablebase`. The crate ships no prober of its own.
+/// Search threads probe one shared instance, hence `Sync`.
+pub trait Tablebase: Send + Sync {
+    /// Largest number of pieces, kings included, the tables cover
+    fn max_pieces(&self) -> u32;
+    /// Verdict for the position, None if it isn't in the tables
+    fn probe_wdl(&self, board: &Board) -> Option<Wdl>;
+    /// Distance to zeroing (plies to the next capture or pawn move on the
+    /// optimal path), signed like the WDL verdict; None if not in the tables
+    fn probe_dtz(&self, board: &Board) -> Option<i32>;
+}
+
+/// Tablebase that knows no positions, for when none is configured
+pub struct NoTablebase;
+
+impl Tablebase for NoTablebase {
+    fn max_pieces(&self) -> u32 {
+        0
+    }
+
+    fn probe_wdl(&self, _board: &Board) -> Option<Wdl> {
+        None
+    }
+
+    fn probe_dtz(&self, _board: &Board) -> Option<i32> {
+        None
+    }
+}
@@ -0,0 +1,58 @@
+// === Time Management ===
+// Module owner: @i3mjagsb
+
+use std::time::Duration;
+
+/// Moves assumed to remain when the GUI doesn't send movestogo
+pub const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// Default for the Move Overhead option: milliseconds lost per move to
+/// communication lag
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 30;
+
+/// Time budget for one move: an even share of the clock plus most of the
+/// increment, less `overhead_ms` for lag. Capped so the remaining clock never
+/// drops below the overhead.
+pub fn allocate_time(time_left_ms: u64, increment_ms: u64, moves_to_go: Option<u64>, overhead_ms: u64) -> Duration {
+    let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let budget = (time_left_ms / moves_to_go + increment_ms * 4 / 5).saturating_sub(overhead_ms);
+    Duration::from_millis(budget.min(time_left_ms.saturating_sub(overhead_ms)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sudden_death_splits_the_clock() {
+        assert_eq!(allocate_time(60_000, 0, None, 0), Duration::from_millis(2_000));
+        assert_eq!(allocate_time(60_000, 0, Some(10), 0), Duration::from_millis(6_000));
+        // The last move before the time control may use everything but the overhead
+        assert_eq!(allocate_time(10_000, 0, Some(1), 30), Duration::from_millis(9_970));
+    }
+
+    #[test]
+    fn increment_adds_to_the_budget() {
+        assert_eq!(allocate_time(60_000, 1_000, None, 0), Duration::from_millis(2_800));
+        assert!(allocate_time(60_000, 1_000, None, 0) > allocate_time(60_000, 0, None, 0));
+    }
+
+    #[test]
+    fn budget_never_exceeds_the_clock() {
+        // A large increment can't be spent before it's been received
+        assert_eq!(allocate_time(100, 2_000, None, 30), Duration::from_millis(70));
+        assert_eq!(allocate_time(10, 0, None, 30), Duration::ZERO);
+    }
+
+    #[test]
+    fn increment_with_and_without_movestogo() {
+        // Sudden death plus increment: a thirtieth of the clock and 80% of the increment
+        assert_eq!(allocate_time(30_000, 2_000, None, 0), Duration::from_millis(2_600));
+        assert_eq!(allocate_time(300_000, 3_000, None, 0), Duration::from_millis(12_400));
+        // Moves to go plus increment
+        assert_eq!(allocate_time(40_000, 1_000, Some(20), 0), Duration::from_millis(2_800));
+        assert_eq!(allocate_time(40_000, 1_000, Some(1), 0), Duration::from_millis(40_000));
+        // Low on time, the increment still can't push the budget past the clock
+        assert_eq!(allocate_time(500, 1_000, Some(5), 50), Duration::from_millis(450));
+    }
+}
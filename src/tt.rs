@@ -0,0 +1,182 @@
+// === Transposition Table ===
+// Module owner: @i3mjagsb
+
+use crate::types::*;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default table size in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// Slots inspected to estimate how full the table is
+const HASHFULL_SAMPLE: usize = 1000;
+
+/// How a stored score relates to the true value of the position
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Bound {
+    #[default]
+    Exact,
+    Lower, // failed high: true score >= score
+    Upper, // failed low: true score <= score
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TTEntry {
+    pub key: u64, // full Zobrist key; 0 marks an empty slot
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+/// One table slot: the entry packed into `data`, and the key stored xored with
+/// it so a slot torn by two threads writing at once fails the key check
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Packed entry layout: score in bits 0-31, depth 32-39, bound 40-41, move 42-57
+const DEPTH_SHIFT: u32 = 32;
+const BOUND_SHIFT: u32 = 40;
+const MOVE_SHIFT: u32 = 42;
+
+/// Move as its 16-bit packed form, with zero for none
+fn pack_move(m: Option<Move>) -> u64 {
+    m.map_or(0, |m| PackedMove::from(m).0 as u64)
+}
+
+fn unpack_move(bits: u64) -> Option<Move> {
+    let packed = PackedMove(bits as u16);
+    (packed.0 != 0).then(|| packed.into())
+}
+
+fn pack(entry: &TTEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    entry.score as u32 as u64 | (entry.depth as u64) << DEPTH_SHIFT | bound << BOUND_SHIFT | pack_move(entry.best_move) << MOVE_SHIFT
+}
+
+fn unpack(key: u64, data: u64) -> TTEntry {
+    TTEntry {
+        key,
+        best_move: unpack_move(data >> MOVE_SHIFT),
+        score: data as u32 as i32,
+        depth: (data >> DEPTH_SHIFT) as u8,
+        bound: match (data >> BOUND_SHIFT) & 3 {
+            1 => Bound::Lower,
+            2 => Bound::Upper,
+            _ => Bound::Exact,
+        },
+    }
+}
+
+/// Fixed-size, power-of-two hash table indexed by the low bits of the Zobrist
+/// key. Probes and stores take `&self`, so search threads can share one table.
+pub struct TranspositionTable {
+    entries: Vec<Slot>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    /// Allocate the largest power-of-two entry count that fits in `mb` megabytes
+    pub fn new(mb: usize) -> Self {
+        let count = Self::entries_for_mb(mb);
+        Self {
+            entries: (0..count).map(|_| Slot::default()).collect(),
+            mask: count - 1,
+        }
+    }
+
+    /// Number of entries a table of `mb` megabytes holds
+    pub fn entries_for_mb(mb: usize) -> usize {
+        let max_entries = (mb.max(1) * 1024 * 1024 / size_of::<Slot>()).max(1);
+        1 << (usize::BITS - 1 - max_entries.leading_zeros())
+    }
+
+    /// Reallocate to a new size, dropping all stored entries
+    pub fn resize(&mut self, mb: usize) {
+        *self = Self::new(mb);
+    }
+
+    /// Number of slots in the table
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Empty every slot, keeping the allocation
+    pub fn clear(&self) {
+        for slot in &self.entries {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Occupied slots per thousand, sampled from the start of the table
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.entries[..self.entries.len().min(HASHFULL_SAMPLE)];
+        let used = sample
+            .iter()
+            .filter(|slot| slot.key.load(Ordering::Relaxed) != 0 || slot.data.load(Ordering::Relaxed) != 0)
+            .count();
+        (used * 1000 / sample.len()) as u32
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let slot = &self.entries[key as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.key.load(Ordering::Relaxed) ^ data == key && key != 0 {
+            Some(unpack(key, data))
+        } else {
+            None
+        }
+    }
+
+    /// Store a result, replacing the slot unless it holds a deeper result for the same position
+    pub fn store(&self, key: u64, best_move: Option<Move>, score: i32, depth: u8, bound: Bound) {
+        let slot = &self.entries[key as usize & self.mask];
+        if let Some(existing) = self.probe(key) {
+            if existing.depth > depth {
+                return;
+            }
+        }
+        let data = pack(&TTEntry { key, best_move, score, depth, bound });
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashfull_counts_stored_entries() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+        // Keys 1..=250 land in the first 250 slots of the sampled thousand
+        for key in 1..=250 {
+            tt.store(key, None, 0, 1, Bound::Exact);
+        }
+        assert_eq!(tt.hashfull(), 250);
+        // Slots past the sample aren't counted
+        tt.store(5000, None, 0, 1, Bound::Exact);
+        assert_eq!(tt.hashfull(), 250);
+        tt.clear();
+        assert_eq!(tt.hashfull(), 0);
+    }
+}
@@ -1,7 +1,11 @@
 // === Shared Types ===
 // Designed by @rea78sbq with input from @i3mjagsb
 
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Piece {
     Pawn = 0,
     Knight = 1,
@@ -11,7 +15,12 @@ pub enum Piece {
     King = 5,
 }
 
+/// Midgame material values in centipawns, indexed by `Piece`. The evaluation
+/// keeps its own endgame values alongside these.
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20000];
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     White = 0,
     Black = 1,
@@ -47,19 +56,204 @@ pub const F8: Square = 61;
 pub const G8: Square = 62;
 pub const H8: Square = 63;
 
+/// File of a square, 0 = a-file
+pub fn file_of(sq: Square) -> u8 {
+    sq % 8
+}
+
+/// Rank of a square, 0 = first rank
+pub fn rank_of(sq: Square) -> u8 {
+    sq / 8
+}
+
+/// King-move (Chebyshev) distance between two squares
+pub fn square_distance(a: Square, b: Square) -> u8 {
+    file_of(a).abs_diff(file_of(b)).max(rank_of(a).abs_diff(rank_of(b)))
+}
+
+/// Parse a square name such as "e4"
+pub fn square_from_algebraic(s: &str) -> Option<Square> {
+    match s.as_bytes() {
+        &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1') * 8 + (file - b'a')),
+        _ => None,
+    }
+}
+
+/// Square name such as "e4"
+pub fn square_to_algebraic(sq: Square) -> String {
+    format!("{}{}", (b'a' + file_of(sq)) as char, (b'1' + rank_of(sq)) as char)
+}
+
 // Castling rights bits
 pub const WHITE_KINGSIDE: u8 = 1;
 pub const WHITE_QUEENSIDE: u8 = 2;
 pub const BLACK_KINGSIDE: u8 = 4;
 pub const BLACK_QUEENSIDE: u8 = 8;
 
-#[derive(Copy, Clone, Debug, Default)]
+/// Index (0..4) of a single castling-right bit, for per-right tables
+pub fn castling_index(right: u8) -> usize {
+    right.trailing_zeros() as usize
+}
+
+/// The castling right a castling move uses: kingside if the king lands on the g-file
+pub fn castling_right_of(m: Move) -> u8 {
+    match (file_of(m.to) == 6, rank_of(m.from) == 0) {
+        (true, true) => WHITE_KINGSIDE,
+        (false, true) => WHITE_QUEENSIDE,
+        (true, false) => BLACK_KINGSIDE,
+        (false, false) => BLACK_QUEENSIDE,
+    }
+}
+
+/// What a move does beyond taking a piece from one square to another
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveKind {
+    #[default]
+    Normal,
+    DoublePush,
+    Castle, // `to` is the king's destination
+    EnPassant,
+    Promotion(Piece),
+    CapturePromotion(Piece),
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Move {
     pub from: Square,
     pub to: Square,
-    pub promotion: Option<Piece>,
-    pub is_castle: bool,
-    pub is_en_passant: bool,
+    pub kind: MoveKind,
+}
+
+impl Move {
+    /// An ordinary move or capture
+    pub const fn new(from: Square, to: Square) -> Self {
+        Move { from, to, kind: MoveKind::Normal }
+    }
+
+    pub const fn with_kind(from: Square, to: Square, kind: MoveKind) -> Self {
+        Move { from, to, kind }
+    }
+
+    /// The piece a pawn promotes to, if this is a promotion
+    pub fn promotion(self) -> Option<Piece> {
+        match self.kind {
+            MoveKind::Promotion(piece) | MoveKind::CapturePromotion(piece) => Some(piece),
+            _ => None,
+        }
+    }
+
+    pub fn is_castle(self) -> bool {
+        self.kind == MoveKind::Castle
+    }
+
+    pub fn is_en_passant(self) -> bool {
+        self.kind == MoveKind::EnPassant
+    }
+
+    pub fn is_double_push(self) -> bool {
+        self.kind == MoveKind::DoublePush
+    }
+}
+
+/// UCI coordinate notation: "e2e4", "e7e8q"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", square_to_algebraic(self.from), square_to_algebraic(self.to))?;
+        if let Some(promo) = self.promotion() {
+            let c = match promo {
+                Piece::Knight => 'n',
+                Piece::Bishop => 'b',
+                Piece::Rook => 'r',
+                _ => 'q',
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParseMoveError;
+
+impl fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid UCI move")
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+/// Parse UCI coordinate notation. The string alone can't tell castling, en passant,
+/// double pushes or capturing promotions apart from plain moves and promotions, so
+/// the kind is only ever `Normal` or `Promotion`; use a board-aware constructor
+/// (e.g. the UCI loop's move parser) when it matters.
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 4 && s.len() != 5 {
+            return Err(ParseMoveError);
+        }
+        let square = |range| s.get(range).and_then(square_from_algebraic).ok_or(ParseMoveError);
+        let from = square(0..2)?;
+        let to = square(2..4)?;
+
+        let promotion = match s.as_bytes().get(4) {
+            None => None,
+            Some(b'q') => Some(Piece::Queen),
+            Some(b'r') => Some(Piece::Rook),
+            Some(b'b') => Some(Piece::Bishop),
+            Some(b'n') => Some(Piece::Knight),
+            Some(_) => return Err(ParseMoveError),
+        };
+
+        Ok(Move::with_kind(from, to, promotion.map_or(MoveKind::Normal, MoveKind::Promotion)))
+    }
+}
+
+/// A `Move` in 16 bits: from in bits 0-5, to in 6-11, kind in 12-15. The kind
+/// is 1 for a double push, 2 for castling, 3 for en passant, or 8 plus the
+/// promotion piece (knight 0 to queen 3), plus 4 more when it captures. Zero is
+/// never a legal move, so it can mark "none".
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackedMove(pub u16);
+
+const PACKED_DOUBLE_PUSH: u16 = 1;
+const PACKED_CASTLE: u16 = 2;
+const PACKED_EN_PASSANT: u16 = 3;
+const PACKED_PROMOTION: u16 = 8;
+const PACKED_CAPTURE: u16 = 4;
+const PROMOTION_PIECES: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+impl From<Move> for PackedMove {
+    fn from(m: Move) -> Self {
+        let flags = match m.kind {
+            MoveKind::Normal => 0,
+            MoveKind::DoublePush => PACKED_DOUBLE_PUSH,
+            MoveKind::Castle => PACKED_CASTLE,
+            MoveKind::EnPassant => PACKED_EN_PASSANT,
+            MoveKind::Promotion(p) => PACKED_PROMOTION | (p as u16 - Piece::Knight as u16),
+            MoveKind::CapturePromotion(p) => PACKED_PROMOTION | PACKED_CAPTURE | (p as u16 - Piece::Knight as u16),
+        };
+        PackedMove(m.from as u16 | (m.to as u16) << 6 | flags << 12)
+    }
+}
+
+impl From<PackedMove> for Move {
+    fn from(packed: PackedMove) -> Self {
+        let flags = packed.0 >> 12;
+        let kind = match flags {
+            PACKED_DOUBLE_PUSH => MoveKind::DoublePush,
+            PACKED_CASTLE => MoveKind::Castle,
+            PACKED_EN_PASSANT => MoveKind::EnPassant,
+            _ if flags & PACKED_PROMOTION == 0 => MoveKind::Normal,
+            _ if flags & PACKED_CAPTURE != 0 => MoveKind::CapturePromotion(PROMOTION_PIECES[(flags & 3) as usize]),
+            _ => MoveKind::Promotion(PROMOTION_PIECES[(flags & 3) as usize]),
+        };
+        Move::with_kind((packed.0 & 63) as Square, ((packed.0 >> 6) & 63) as Square, kind)
+    }
 }
 
 /// Iterator over set bits in a Bitboard
@@ -80,19 +274,117 @@ impl Iterator for BitIter {
 }
 
 /// Core trait for chess board implementations
-/// Clone bound added by @i3mjagsb for search tree exploration
-pub trait ChessBoard: Clone {
+/// Clone bound added by @i3mjagsb for search tree exploration, Send so
+/// search threads can each take a copy
+pub trait ChessBoard: Clone + Send {
     fn piece_at(&self, sq: Square) -> Option<(Piece, Color)>;
     fn pieces(&self, color: Color, piece: Piece) -> Bitboard;
     fn occupancy(&self, color: Color) -> Bitboard;
     fn side_to_move(&self) -> Color;
     fn make_move(&mut self, m: Move);
     fn unmake_move(&mut self);
+    /// Pass the turn: flips the side to move, clears en passant and counts
+    /// toward the fifty-move rule. Callers must not pass while in check.
+    fn make_null_move(&mut self);
+    /// Undo the last `make_null_move` exactly, hash included
+    fn unmake_null_move(&mut self);
     fn is_capture(&self, m: Move) -> bool;
     fn halfmove_clock(&self) -> u8;
+    /// Fifty moves by each side without a capture or pawn move: a draw can be claimed
+    fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock() >= 100
+    }
     fn zobrist_hash(&self) -> u64;
+    /// Whether the current position already occurred earlier in the move history
+    fn is_repetition(&self) -> bool;
     fn is_in_check(&self, color: Color) -> bool;
     fn castling_rights(&self) -> u8;
+    /// Starting square of the rook that castles for a single right bit
+    /// (fixed corners in standard chess, anywhere on the back rank in Chess960)
+    fn castling_rook(&self, right: u8) -> Square;
     fn en_passant_square(&self) -> Option<Square>;
     fn is_square_attacked(&self, sq: Square, by_color: Color) -> bool;
+    /// Every `by_color` piece attacking `sq` with sliders blocked only by
+    /// `occupied`, so removing pieces from it reveals x-ray attackers
+    fn attackers_through(&self, sq: Square, by_color: Color, occupied: Bitboard) -> Bitboard;
+    /// Number of `color`'s pieces of one type
+    fn piece_count(&self, color: Color, piece: Piece) -> u32 {
+        self.pieces(color, piece).count_ones()
+    }
+    /// Midgame value of all of `color`'s pieces except the king
+    fn material(&self, color: Color) -> i32 {
+        self.non_pawn_material(color) + self.piece_count(color, Piece::Pawn) as i32 * PIECE_VALUES[Piece::Pawn as usize]
+    }
+    /// `material` without the pawns
+    fn non_pawn_material(&self, color: Color) -> i32 {
+        [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+            .iter()
+            .map(|&p| self.piece_count(color, p) as i32 * PIECE_VALUES[p as usize])
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promotions_round_trip_through_uci_text() {
+        for (text, piece) in [("e7e8q", Piece::Queen), ("e7e8r", Piece::Rook), ("a2a1b", Piece::Bishop), ("g7h8n", Piece::Knight)] {
+            let m: Move = text.parse().expect("valid move");
+            assert_eq!(m.promotion(), Some(piece));
+            assert_eq!(m.to_string(), text);
+        }
+        let m: Move = "e2e4".parse().expect("valid move");
+        assert_eq!(m, Move::new(12, 28));
+        assert_eq!(m.to_string(), "e2e4");
+    }
+
+    #[test]
+    fn malformed_uci_moves_are_rejected() {
+        for text in ["", "e2", "e2e", "e2-e4", "e2e4qq", "e2e4k", "e7e8Q", "i2e4", "e2e9", "E2E4"] {
+            assert_eq!(text.parse::<Move>(), Err(ParseMoveError), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn corner_squares_convert_both_ways() {
+        for (name, sq, file, rank) in [("a1", 0, 0, 0), ("h1", 7, 7, 0), ("a8", 56, 0, 7), ("h8", 63, 7, 7)] {
+            assert_eq!(square_from_algebraic(name), Some(sq));
+            assert_eq!(square_to_algebraic(sq), name);
+            assert_eq!((file_of(sq), rank_of(sq)), (file, rank));
+        }
+        for sq in 0..64 {
+            assert_eq!(square_from_algebraic(&square_to_algebraic(sq)), Some(sq));
+        }
+    }
+
+    #[test]
+    fn invalid_square_names_are_rejected() {
+        for name in ["z9", "i1", "a0", "a9", "A1", "e", "e44", "", "4e"] {
+            assert_eq!(square_from_algebraic(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn packed_moves_round_trip() {
+        let mut moves = vec![
+            Move::new(12, 20),
+            Move::with_kind(12, 28, MoveKind::DoublePush),
+            Move::with_kind(4, 6, MoveKind::Castle),
+            Move::with_kind(60, 58, MoveKind::Castle),
+            Move::with_kind(36, 43, MoveKind::EnPassant),
+            Move::with_kind(27, 18, MoveKind::EnPassant),
+            Move::new(0, 63),
+        ];
+        for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+            moves.push(Move::with_kind(52, 60, MoveKind::Promotion(piece)));
+            moves.push(Move::with_kind(14, 7, MoveKind::CapturePromotion(piece)));
+        }
+        for m in moves {
+            let packed = PackedMove::from(m);
+            assert_ne!(packed, PackedMove::default());
+            assert_eq!(Move::from(packed), m);
+        }
+    }
 }
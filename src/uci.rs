@@ -1,16 +1,100 @@
 // === UCI Protocol ===
 // Module owner: @i3mjagsb
 
-use crate::board::Board;
-use crate::search::search;
+use crate::board::{Board, START_FEN};
+use crate::book::Book;
+use crate::eval;
+use crate::search::{mate_in, PvLine, SearchInfo, SearchLimits, SearchResult, SearchState, SearchStats, MAX_THREADS};
+use crate::time::{allocate_time, DEFAULT_MOVE_OVERHEAD_MS};
+use crate::tt::DEFAULT_HASH_MB;
 use crate::movegen::generate_moves;
+use crate::polyglot::{PolyglotBook, PolyglotKeys};
 use crate::types::*;
 use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Fixed workload for `bench`: openings, middlegames, tactics and endgames
+const BENCH_POSITIONS: [&str; 10] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "2r3k1/pp3ppp/4p3/3pP3/1P1n4/P2B4/5PPP/2R3K1 b - - 0 25",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/1r3PPP/3R2K1 w - - 0 1",
+];
+
+/// Search depth used by `bench` unless one is given
+const BENCH_DEPTH: u8 = 6;
+
+/// Values set through `setoption`
+struct EngineOptions {
+    multipv: usize,
+    chess960: bool,
+    own_book: bool,
+    book: Option<OpeningBook>, // loaded from BookFile
+    debug: bool,               // set by `debug on`: extra info strings
+    move_overhead: u64,        // milliseconds, taken off every time budget
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            multipv: 1,
+            chess960: false,
+            own_book: false,
+            book: None,
+            debug: false,
+            move_overhead: DEFAULT_MOVE_OVERHEAD_MS,
+        }
+    }
+}
+
+/// Book loaded through BookFile: Polyglot for `.bin` files, else our text format
+enum OpeningBook {
+    Text(Book),
+    Polyglot(PolyglotBook),
+}
+
+impl OpeningBook {
+    fn load(path: &str) -> io::Result<Self> {
+        if path.to_ascii_lowercase().ends_with(".bin") {
+            Ok(OpeningBook::Polyglot(PolyglotBook::open(path, PolyglotKeys::default())?))
+        } else {
+            Ok(OpeningBook::Text(Book::load(path)?))
+        }
+    }
+
+    fn book_move(&self, board: &Board) -> Option<Move> {
+        match self {
+            OpeningBook::Text(book) => book.book_move(board),
+            OpeningBook::Polyglot(book) => book.book_move(board),
+        }
+    }
+}
+
+/// A search running on a background thread. It holds the shared search
+/// state's lock until it finishes.
+struct SearchJob {
+    handle: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    infinite: bool,
+}
 
 pub fn uci_loop() {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
     let mut board = Board::new();
+    let state = Arc::new(Mutex::new(SearchState::new()));
+    let mut job: Option<SearchJob> = None;
+    let mut options = EngineOptions::default();
 
     for line in stdin.lock().lines() {
         let input = match line {
@@ -27,43 +111,285 @@ pub fn uci_loop() {
             "uci" => {
                 println!("id name AgentChat-Chess");
                 println!("id author AgentChat Team (@rea78sbq @rpbr2qqf @mnovzrkb @i3mjagsb)");
+                println!("option name Hash type spin default {} min 1 max 1024", DEFAULT_HASH_MB);
+                println!("option name Clear Hash type button");
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!("option name MultiPV type spin default 1 min 1 max 256");
+                println!("option name Move Overhead type spin default {} min 0 max 5000", DEFAULT_MOVE_OVERHEAD_MS);
+                println!("option name Ponder type check default false");
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                println!("option name UCI_Chess960 type check default false");
                 println!("uciok");
             }
             "isready" => println!("readyok"),
-            "ucinewgame" => board = Board::new(),
-            "position" => parse_position(&mut board, &tokens),
+            "debug" => set_debug(&mut options, &tokens),
+            "setoption" => {
+                finish_search(&mut job);
+                set_option(&mut options, &mut state.lock().expect("search thread panicked"), &tokens);
+            }
+            "ucinewgame" => {
+                finish_search(&mut job);
+                board.set_fen(&START_FEN);
+                state.lock().expect("search thread panicked").new_game();
+            }
+            "position" => {
+                finish_search(&mut job);
+                parse_position(&mut board, &tokens);
+                if options.chess960 {
+                    board.set_chess960(true);
+                }
+            }
             "go" => {
-                let depth = parse_depth(&tokens);
-                let moves = generate_moves(&board);
-                if moves.is_empty() {
-                    if board.is_in_check(board.side_to_move()) {
-                        println!("info string checkmate");
-                    } else {
-                        println!("info string stalemate");
-                    }
+                finish_search(&mut job);
+                let params = parse_go(&tokens);
+                if board.is_checkmate() {
+                    println!("info string checkmate");
+                    println!("bestmove 0000");
+                } else if board.is_stalemate() {
+                    println!("info string stalemate");
                     println!("bestmove 0000");
+                } else if let Some(m) = book_move(&board, &params, &options) {
+                    println!("info string book move");
+                    println!("bestmove {}", move_to_uci(&board, m));
                 } else {
-                    let (m, score) = search(&mut board, depth);
-                    println!("info depth {} score cp {}", depth, score);
-                    println!("bestmove {}", move_to_uci(m));
+                    job = Some(start_search(&board, &state, &params, &options));
+                }
+            }
+            "stop" => finish_search(&mut job),
+            "ponderhit" => {
+                if let Some(job) = &job {
+                    job.ponder.store(false, Ordering::Relaxed);
                 }
             }
             "perft" => {
-                let depth = if tokens.len() > 1 {
-                    tokens[1].parse().unwrap_or(1)
+                let divide = tokens.get(1) == Some(&"divide");
+                let depth_token = if divide { tokens.get(2) } else { tokens.get(1) };
+                let depth = depth_token.and_then(|t| t.parse().ok()).unwrap_or(1);
+                if divide {
+                    let mut total = 0;
+                    for (m, count) in crate::movegen::perft_divide(&mut board, depth) {
+                        println!("{}: {}", move_to_uci(&board, m), count);
+                        total += count;
+                    }
+                    println!();
+                    println!("Nodes searched: {}", total);
                 } else {
-                    1
-                };
-                let count = crate::movegen::perft(&mut board, depth);
-                println!("Nodes searched: {}", count);
+                    let count = crate::movegen::perft(&mut board, depth);
+                    println!("Nodes searched: {}", count);
+                }
+            }
+            "quit" => {
+                finish_search(&mut job);
+                break;
             }
-            "quit" => break,
             "d" => debug_print(&board),
+            "eval" => eval_print(&board),
+            "bench" => {
+                finish_search(&mut job);
+                let depth = tokens.get(1).and_then(|t| t.parse().ok()).unwrap_or(BENCH_DEPTH);
+                bench(depth);
+            }
             _ => {}
         }
 
         stdout.flush().ok();
     }
+
+    // Input closed without quit: let a bounded search finish and print its move
+    if let Some(job) = job.take() {
+        if job.infinite || job.ponder.load(Ordering::Relaxed) {
+            job.stop.store(true, Ordering::Relaxed);
+        }
+        job.handle.join().ok();
+    }
+}
+
+/// Run a search on a background thread; it prints `bestmove` when done
+fn start_search(board: &Board, state: &Arc<Mutex<SearchState>>, params: &GoParams, options: &EngineOptions) -> SearchJob {
+    let mut board = board.clone();
+    let mut limits = search_limits(params, board.side_to_move(), options.move_overhead);
+    limits.multipv = options.multipv;
+    let infinite = params.infinite;
+    let debug = options.debug;
+    let (stop, ponder) = {
+        let state = state.lock().expect("search thread panicked");
+        (state.stop_flag(), state.ponder_flag())
+    };
+    stop.store(false, Ordering::Relaxed);
+    ponder.store(params.ponder, Ordering::Relaxed);
+
+    let state = Arc::clone(state);
+    let thread_stop = Arc::clone(&stop);
+    let thread_ponder = Arc::clone(&ponder);
+    let handle = thread::spawn(move || {
+        let mut state = state.lock().expect("search thread panicked");
+        let info_board = board.clone();
+        let multipv = limits.multipv > 1;
+        state.set_info_callback(move |info| {
+            for (k, line) in info.lines.iter().enumerate() {
+                let multipv = multipv.then_some(k + 1);
+                println!("{}", info_line(&info_board, multipv, info, line));
+            }
+        });
+        let currmove_board = board.clone();
+        state.set_currmove_callback(move |depth, m, number| {
+            println!("{}", currmove_line(&currmove_board, depth, m, number));
+        });
+        let result = state.search_ex(&mut board, &limits);
+        if let Some(line) = debug_line(debug, &state.stats()) {
+            println!("{}", line);
+        }
+
+        // In infinite and ponder modes bestmove must wait for the GUI's stop or ponderhit
+        while (infinite || thread_ponder.load(Ordering::Relaxed)) && !thread_stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        println!("{}", bestmove_line(&board, result.best_move, ponder_move(&state, &board, &result)));
+    });
+    SearchJob { handle, stop, ponder, infinite }
+}
+
+/// `info [multipv K] depth D seldepth S score (cp S | mate M) nodes N nps R hashfull H time T pv ...`
+fn info_line(board: &Board, multipv: Option<usize>, info: &SearchInfo, line: &PvLine) -> String {
+    let mut text = String::from("info");
+    if let Some(k) = multipv {
+        text.push_str(&format!(" multipv {}", k));
+    }
+    let score = match mate_in(line.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", line.score),
+    };
+    let ms = info.elapsed.as_millis() as u64;
+    let nps = info.nodes * 1000 / ms.max(1);
+    text.push_str(&format!(
+        " depth {} seldepth {} score {} nodes {} nps {} hashfull {} time {}",
+        info.depth, info.seldepth, score, info.nodes, nps, info.hashfull, ms
+    ));
+    if !line.moves.is_empty() {
+        let pv: Vec<String> = line.moves.iter().map(|&m| move_to_uci(board, m)).collect();
+        text.push_str(&format!(" pv {}", pv.join(" ")));
+    }
+    text
+}
+
+/// `debug on` or `debug off`
+fn set_debug(options: &mut EngineOptions, tokens: &[&str]) {
+    options.debug = tokens.get(1) == Some(&"on");
+}
+
+/// Diagnostics printed after a search, only in debug mode
+fn debug_line(debug: bool, stats: &SearchStats) -> Option<String> {
+    debug.then(|| stats_line(stats))
+}
+
+/// `info string` with the search's table hit rate and move ordering quality
+fn stats_line(stats: &SearchStats) -> String {
+    let percent = |part: u64, whole: u64| part * 100 / whole.max(1);
+    format!(
+        "info string tt hits {}/{} ({}%) first-move cutoffs {}/{} ({}%)",
+        stats.tt_hits,
+        stats.tt_probes,
+        percent(stats.tt_hits, stats.tt_probes),
+        stats.first_move_cutoffs,
+        stats.cutoffs,
+        percent(stats.first_move_cutoffs, stats.cutoffs)
+    )
+}
+
+/// `info depth D currmove M currmovenumber N`, N counting from 1
+fn currmove_line(board: &Board, depth: u8, m: Move, number: usize) -> String {
+    format!("info depth {} currmove {} currmovenumber {}", depth, move_to_uci(board, m), number)
+}
+
+/// A move from the opening book, if enabled and the position is in it. Infinite
+/// and ponder searches are analysis, so they always search.
+fn book_move(board: &Board, params: &GoParams, options: &EngineOptions) -> Option<Move> {
+    if !options.own_book || params.infinite || params.ponder {
+        return None;
+    }
+    options.book.as_ref()?.book_move(board)
+}
+
+/// Reply expected after the best move: the second move of the principal
+/// variation, or the transposition table's move if the PV stops short
+fn ponder_move(state: &SearchState, board: &Board, result: &SearchResult) -> Option<Move> {
+    if let Some(&reply) = result.pv.get(1) {
+        return Some(reply);
+    }
+    let mut after = board.clone();
+    after.make_move(result.best_move);
+    let tt_move = state.tt().probe(after.zobrist_hash())?.best_move?;
+    generate_moves(&after).contains(&tt_move).then_some(tt_move)
+}
+
+/// `bestmove M [ponder P]`
+fn bestmove_line(board: &Board, best: Move, ponder: Option<Move>) -> String {
+    let mut line = format!("bestmove {}", move_to_uci(board, best));
+    if let Some(reply) = ponder {
+        let mut after = board.clone();
+        after.make_move(best);
+        line.push_str(&format!(" ponder {}", move_to_uci(&after, reply)));
+    }
+    line
+}
+
+/// Handle `setoption name <id> [value <x>]`; option names may contain spaces
+fn set_option(options: &mut EngineOptions, state: &mut SearchState, tokens: &[&str]) {
+    let value_idx = tokens.iter().position(|&t| t == "value").unwrap_or(tokens.len());
+    let name = tokens.get(2..value_idx).unwrap_or(&[]).join(" ");
+    let value = tokens.get(value_idx + 1..).unwrap_or(&[]).join(" ");
+
+    match name.to_ascii_lowercase().as_str() {
+        "hash" => {
+            if let Ok(mb) = value.parse::<usize>() {
+                state.set_hash_size(mb.clamp(1, 1024));
+            }
+        }
+        "clear hash" => state.clear_hash(),
+        "threads" => {
+            if let Ok(n) = value.parse::<usize>() {
+                state.set_threads(n);
+            }
+        }
+        "multipv" => {
+            if let Ok(n) = value.parse::<usize>() {
+                options.multipv = n.clamp(1, 256);
+            }
+        }
+        "move overhead" => {
+            if let Ok(ms) = value.parse::<u64>() {
+                options.move_overhead = ms.min(5000);
+            }
+        }
+        "ponder" => {} // pondering is driven by go ponder; nothing to configure
+        "ownbook" => options.own_book = value.eq_ignore_ascii_case("true"),
+        "bookfile" => {
+            options.book = None;
+            if !value.is_empty() && value != "<empty>" {
+                match OpeningBook::load(&value) {
+                    Ok(book) => {
+                        match &book {
+                            OpeningBook::Text(b) => println!("info string loaded {} book positions from {}", b.len(), value),
+                            OpeningBook::Polyglot(b) => println!("info string loaded {} book entries from {}", b.len(), value),
+                        }
+                        options.book = Some(book);
+                    }
+                    Err(e) => println!("info string cannot read book {}: {}", value, e),
+                }
+            }
+        }
+        "uci_chess960" => options.chess960 = value.eq_ignore_ascii_case("true"),
+        _ => println!("info string unknown option {}", name),
+    }
+}
+
+/// Stop the running search (if any) and wait for it to release the state
+fn finish_search(job: &mut Option<SearchJob>) {
+    if let Some(job) = job.take() {
+        job.stop.store(true, Ordering::Relaxed);
+        job.handle.join().expect("search thread panicked");
+    }
 }
 
 fn parse_position(board: &mut Board, tokens: &[&str]) {
@@ -73,117 +399,213 @@ fn parse_position(board: &mut Board, tokens: &[&str]) {
     }
 
     if tokens[i] == "startpos" {
-        *board = Board::new();
+        board.set_fen(&START_FEN);
         i += 1;
     } else if tokens[i] == "fen" {
         i += 1;
-        if i + 5 < tokens.len() {
-            *board = parse_fen(&tokens[i..i+6]);
-            i += 6;
+        // Everything up to `moves` is the FEN: six fields, or four or five
+        // with the clocks defaulted. The parser rejects any other count.
+        let fields = tokens[i..].iter().take_while(|&&t| t != "moves").count();
+        if let Err(e) = board.try_set_fen(&tokens[i..i + fields].join(" ")) {
+            // Leave the board alone rather than play moves on a guessed position
+            println!("info string invalid fen: {}", e);
+            return;
         }
+        i += fields;
     }
 
     if i < tokens.len() && tokens[i] == "moves" {
         i += 1;
         while i < tokens.len() {
-            if let Some(m) = uci_to_move(board, tokens[i]) {
-                board.make_move(m);
+            // Later moves make no sense once one fails, so stop there
+            let Some(m) = uci_to_move(board, tokens[i]) else {
+                println!("info string unreadable move {}", tokens[i]);
+                break;
+            };
+            if let Err(e) = board.try_make_move(m) {
+                println!("info string rejected move {}: {}", tokens[i], e);
+                break;
             }
             i += 1;
         }
     }
+    // Long games would otherwise carry every move into each search's board clone
+    board.reset_history();
 }
 
-fn parse_fen(parts: &[&str]) -> Board {
-    // parts: [pieces, side, castling, en_passant, halfmove, fullmove]
-    // For now, create a board from FEN by parsing piece positions
-    let mut board = Board::from_fen(parts);
-    board
+/// Parameters of a `go` command
+#[derive(Debug, Default)]
+struct GoParams {
+    depth: Option<u8>,
+    wtime: Option<u64>,
+    btime: Option<u64>,
+    winc: Option<u64>,
+    binc: Option<u64>,
+    movestogo: Option<u64>,
+    movetime: Option<u64>,
+    nodes: Option<u64>,
+    infinite: bool,
+    ponder: bool,
 }
 
-fn parse_depth(tokens: &[&str]) -> u8 {
-    for (i, &token) in tokens.iter().enumerate() {
-        if token == "depth" && i + 1 < tokens.len() {
-            return tokens[i + 1].parse().unwrap_or(6);
+fn parse_go(tokens: &[&str]) -> GoParams {
+    let mut params = GoParams::default();
+    let mut i = 1;
+    while i < tokens.len() {
+        let value = tokens.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+        match tokens[i] {
+            "depth" => params.depth = value.map(|d| d.min(u8::MAX as u64) as u8),
+            "wtime" => params.wtime = value,
+            "btime" => params.btime = value,
+            "winc" => params.winc = value,
+            "binc" => params.binc = value,
+            "movestogo" => params.movestogo = value,
+            "movetime" => params.movetime = value,
+            "nodes" => params.nodes = value,
+            "infinite" => {
+                params.infinite = true;
+                i += 1;
+                continue;
+            }
+            "ponder" => {
+                params.ponder = true;
+                i += 1;
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
         }
+        i += 2;
     }
-    6 // default depth
+    params
 }
 
-fn move_to_uci(m: Move) -> String {
-    let from_file = (b'a' + m.from % 8) as char;
-    let from_rank = (b'1' + m.from / 8) as char;
-    let to_file = (b'a' + m.to % 8) as char;
-    let to_rank = (b'1' + m.to / 8) as char;
+/// Clock-based budget for the side to move, with movetime and nodes as hard caps;
+/// a fixed depth when there is no limit at all
+fn search_limits(params: &GoParams, side: Color, move_overhead: u64) -> SearchLimits {
+    let (time, inc) = match side {
+        Color::White => (params.wtime, params.winc),
+        Color::Black => (params.btime, params.binc),
+    };
+    let time = time.map(|t| allocate_time(t, inc.unwrap_or(0), params.movestogo, move_overhead));
+    let movetime = params.movetime.map(Duration::from_millis);
+    let depth = match (params.depth, time, movetime, params.nodes) {
+        (None, None, None, None) if !params.infinite => Some(6), // default depth
+        (depth, ..) => depth,
+    };
+    SearchLimits { depth, time, movetime, nodes: params.nodes, ..Default::default() }
+}
 
-    let mut s = format!("{}{}{}{}", from_file, from_rank, to_file, to_rank);
-    if let Some(promo) = m.promotion {
-        s.push(match promo {
-            Piece::Queen => 'q',
-            Piece::Rook => 'r',
-            Piece::Bishop => 'b',
-            Piece::Knight => 'n',
-            _ => 'q',
-        });
+/// UCI notation for a move; in Chess960 mode castling is written king-onto-rook
+fn move_to_uci(board: &Board, m: Move) -> String {
+    if m.is_castle() && board.is_chess960() {
+        let rook = board.castling_rook(castling_right_of(m));
+        return Move { to: rook, ..m }.to_string();
     }
-    s
+    m.to_string()
 }
 
+/// Parse a UCI move and fill in its kind (castling, en passant, double push or
+/// capturing promotion) from the position
 fn uci_to_move(board: &Board, s: &str) -> Option<Move> {
-    let bytes = s.as_bytes();
-    if bytes.len() < 4 {
-        return None;
-    }
-
-    let from_file = bytes[0].wrapping_sub(b'a');
-    let from_rank = bytes[1].wrapping_sub(b'1');
-    let to_file = bytes[2].wrapping_sub(b'a');
-    let to_rank = bytes[3].wrapping_sub(b'1');
+    let mut m: Move = s.parse().ok()?;
+    let us = board.side_to_move();
 
-    if from_file > 7 || from_rank > 7 || to_file > 7 || to_rank > 7 {
-        return None;
+    match board.piece_at(m.from) {
+        // Castling: king onto its own castling rook (Chess960 style), or the
+        // standard two-square king move outside Chess960
+        Some((Piece::King, _)) => {
+            let back_rank = m.from - file_of(m.from);
+            let (kingside, queenside) = match us {
+                Color::White => (WHITE_KINGSIDE, WHITE_QUEENSIDE),
+                Color::Black => (BLACK_KINGSIDE, BLACK_QUEENSIDE),
+            };
+            let onto_rook = [kingside, queenside]
+                .into_iter()
+                .find(|&right| board.castling_rights() & right != 0 && board.castling_rook(right) == m.to);
+            if let Some(right) = onto_rook {
+                m = Move::with_kind(m.from, back_rank + if right == kingside { 6 } else { 2 }, MoveKind::Castle);
+            } else if !board.is_chess960()
+                && ((m.from == E1 && (m.to == G1 || m.to == C1)) || (m.from == E8 && (m.to == G8 || m.to == C8)))
+            {
+                m.kind = MoveKind::Castle;
+            }
+        }
+        Some((Piece::Pawn, _)) => {
+            let capture = board.piece_at(m.to).is_some_and(|(_, color)| color != us);
+            m.kind = match m.kind {
+                MoveKind::Promotion(piece) if capture => MoveKind::CapturePromotion(piece),
+                MoveKind::Normal if board.en_passant_square() == Some(m.to) => MoveKind::EnPassant,
+                MoveKind::Normal if m.from.abs_diff(m.to) == 16 => MoveKind::DoublePush,
+                kind => kind,
+            };
+        }
+        _ => {}
     }
 
-    let from = from_rank * 8 + from_file;
-    let to = to_rank * 8 + to_file;
+    Some(m)
+}
 
-    let promotion = if bytes.len() > 4 {
-        match bytes[4] {
-            b'q' => Some(Piece::Queen),
-            b'r' => Some(Piece::Rook),
-            b'b' => Some(Piece::Bishop),
-            b'n' => Some(Piece::Knight),
-            _ => None,
-        }
-    } else {
-        None
-    };
+/// Search every bench position to `depth` from a fresh state and report the
+/// total node count and speed, for comparing performance across builds.
+/// Returns the node count.
+fn bench(depth: u8) -> u64 {
+    let mut state = SearchState::new();
+    let mut nodes = 0;
+    let start = Instant::now();
+    for fen in BENCH_POSITIONS {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let mut board = Board::from_fen(&fields);
+        state.new_game();
+        let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+        nodes += state.search_ex(&mut board, &limits).nodes;
+    }
+    let elapsed = start.elapsed().as_secs_f64().max(1e-6);
+    println!("{} nodes {} nps", nodes, (nodes as f64 / elapsed) as u64);
+    nodes
+}
 
-    // Check if this is a castling move
-    let is_castle = if let Some((Piece::King, _)) = board.piece_at(from) {
-        (from == E1 && (to == G1 || to == C1)) || (from == E8 && (to == G8 || to == C8))
-    } else {
-        false
+/// Static evaluation with a per-term breakdown. The table is in centipawns from
+/// White's side; the final evaluation line is from the side to move.
+fn eval_print(board: &Board) {
+    let phase = eval::game_phase(board);
+    let rule = "-----------------+---------+---------+--------";
+    let row = |name: &str, mg: String, eg: String, blended: i32| {
+        println!("{:>16} | {:>7} | {:>7} | {:>7}", name, mg, eg, blended);
     };
 
-    // Check if this is en passant
-    let is_en_passant = if let Some((Piece::Pawn, _)) = board.piece_at(from) {
-        if let Some(ep_sq) = board.en_passant_square() {
-            to == ep_sq
-        } else {
-            false
-        }
-    } else {
-        false
-    };
-
-    Some(Move {
-        from,
-        to,
-        promotion,
-        is_castle,
-        is_en_passant,
-    })
+    println!("{:>16} | {:>7} | {:>7} | {:>7}", "Term", "MG", "EG", "Blended");
+    println!("{}", rule);
+    let (mut mg, mut eg) = (0, 0);
+    let params = eval::EvalParams::default();
+    for (name, term_mg, term_eg) in eval::eval_terms(board, &params) {
+        let blended = eval::blend(term_mg, term_eg, phase);
+        row(name, term_mg.to_string(), term_eg.to_string(), blended);
+        mg += term_mg;
+        eg += term_eg;
+    }
+    let check = eval::check_penalty(board, &params);
+    if check != 0 {
+        row("In check", String::new(), String::new(), check);
+    }
+    let tempo = eval::tempo(board, &params);
+    row("Tempo", String::new(), String::new(), tempo);
+    println!("{}", rule);
+    let total = eval::blend(mg, eg, phase) + check + tempo;
+    row("Total", mg.to_string(), eg.to_string(), total);
+    // KPK and lone-king mates are scored by their own rules instead of the terms
+    let endgame = eval::kpk_score(board)
+        .map(|score| ("KPK bitbase", score))
+        .or_else(|| eval::mating_score(board).map(|score| ("Mating net", score)));
+    if let Some((name, score)) = endgame {
+        row(name, String::new(), String::new(), score);
+        println!("{:>16}   ({} replaces the total above)", "", name);
+    }
+    println!();
+    println!("Phase: {} / {}", phase, eval::MAX_PHASE);
+    println!("Evaluation: {} cp (side to move)", eval::evaluate(board));
 }
 
 fn debug_print(board: &Board) {
@@ -227,13 +649,295 @@ fn debug_print(board: &Board) {
     println!();
 
     if let Some(ep) = board.en_passant_square() {
-        let ep_file = (b'a' + ep % 8) as char;
-        let ep_rank = (b'1' + ep / 8) as char;
-        println!("En passant: {}{}", ep_file, ep_rank);
+        println!("En passant: {}", square_to_algebraic(ep));
     }
 
+    println!("Fen: {}", board);
+
     if board.is_in_check(board.side_to_move()) {
         println!("CHECK!");
     }
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tt::Bound;
+
+    fn position(board: &mut Board, command: &str) {
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        parse_position(board, &tokens);
+    }
+
+    #[test]
+    fn position_fen_without_moves() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new();
+        position(&mut board, &format!("position fen {}", fen));
+        assert_eq!(board.to_fen(), fen);
+
+        let mut board = Board::new();
+        position(&mut board, "position fen 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -");
+        assert_eq!(board.to_fen(), "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let mut board = Board::new();
+        position(&mut board, "position startpos moves e2e4 c7c5 g1f3");
+        assert_eq!(board.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        let mut board = Board::new();
+        position(&mut board, "position fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 5 20 moves e1g1 e8c8");
+        assert_eq!(board.to_fen(), "2kr3r/8/8/8/8/8/8/R4RK1 w - - 7 21");
+
+        // Four fields: the clocks default to 0 and 1
+        let mut board = Board::new();
+        position(&mut board, "position fen 4k3/8/8/8/8/8/4P3/4K3 w - - moves e2e4 e8d7");
+        assert_eq!(board.to_fen(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+    }
+
+    fn go(command: &str) -> GoParams {
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        parse_go(&tokens)
+    }
+
+    #[test]
+    fn go_movetime_is_parsed() {
+        assert_eq!(go("go movetime 250").movetime, Some(250));
+        assert_eq!(go("go depth 8").movetime, None);
+
+        // With a depth as well, both limits apply and whichever comes first wins
+        let limits = search_limits(&go("go depth 8 movetime 250"), Color::White, 0);
+        assert_eq!(limits.depth, Some(8));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn short_movetime_returns_quickly() {
+        let mut board = Board::new();
+        let limits = search_limits(&go("go movetime 100"), board.side_to_move(), 0);
+        assert_eq!(limits.depth, None);
+        let start = Instant::now();
+        let result = SearchState::new().search_ex(&mut board, &limits);
+        assert!(start.elapsed() < Duration::from_secs(1), "took {:?}", start.elapsed());
+        assert!(board.legal_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn hash_option_sizes_the_table() {
+        let mut options = EngineOptions::default();
+        let mut state = SearchState::new();
+        let mut set = |command: &str| {
+            let tokens: Vec<&str> = command.split_whitespace().collect();
+            set_option(&mut options, &mut state, &tokens);
+            state.tt().len()
+        };
+        // 16-byte slots: 16 MB holds exactly 2^20, 3 MB rounds down to 2^17
+        assert_eq!(set("setoption name Hash value 16"), 1 << 20);
+        assert_eq!(set("setoption name Hash value 3"), 1 << 17);
+        assert_eq!(set("setoption name Hash value 1"), 1 << 16);
+        // Out-of-range values are clamped to the advertised minimum
+        assert_eq!(set("setoption name Hash value 0"), 1 << 16);
+    }
+
+    #[test]
+    fn clear_hash_option_empties_the_table() {
+        let mut options = EngineOptions::default();
+        let mut state = SearchState::new();
+        let mut set = |state: &mut SearchState, command: &str| {
+            let tokens: Vec<&str> = command.split_whitespace().collect();
+            set_option(&mut options, state, &tokens);
+        };
+        set(&mut state, "setoption name Hash value 1");
+        let mut board = Board::new();
+        state.search(&mut board, 5);
+        let key = board.zobrist_hash();
+        state.tt().store(key, None, 25, 4, Bound::Exact);
+        assert!(state.tt().probe(key).is_some());
+        assert!(state.tt().hashfull() > 0);
+        let slots = state.tt().len();
+
+        set(&mut state, "setoption name Clear Hash");
+        assert!(state.tt().probe(key).is_none());
+        assert_eq!(state.tt().hashfull(), 0);
+        assert_eq!(state.tt().len(), slots);
+    }
+
+    #[test]
+    fn debug_toggles_search_diagnostics() {
+        let mut options = EngineOptions::default();
+        let mut state = SearchState::new();
+        state.search(&mut Board::new(), 4);
+        assert!(debug_line(options.debug, &state.stats()).is_none());
+
+        set_debug(&mut options, &["debug", "on"]);
+        let line = debug_line(options.debug, &state.stats()).expect("debug output");
+        assert!(line.starts_with("info string tt hits "), "{}", line);
+
+        set_debug(&mut options, &["debug", "off"]);
+        assert!(debug_line(options.debug, &state.stats()).is_none());
+    }
+
+    #[test]
+    fn move_overhead_shrinks_the_budget() {
+        let mut options = EngineOptions::default();
+        let mut state = SearchState::new();
+        let params = go("go wtime 60000 btime 60000 winc 1000 binc 1000");
+        let budget = |options: &EngineOptions| search_limits(&params, Color::White, options.move_overhead).time;
+        assert_eq!(budget(&options), Some(Duration::from_millis(2_800 - DEFAULT_MOVE_OVERHEAD_MS)));
+
+        let tokens: Vec<&str> = "setoption name Move Overhead value 1000".split_whitespace().collect();
+        set_option(&mut options, &mut state, &tokens);
+        assert_eq!(budget(&options), Some(Duration::from_millis(1_800)));
+
+        // An overhead bigger than the share of the clock leaves nothing, not less
+        let tokens: Vec<&str> = "setoption name Move Overhead value 5000".split_whitespace().collect();
+        set_option(&mut options, &mut state, &tokens);
+        assert_eq!(budget(&options), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn eval_command_prints_without_panicking() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+            // Scored by the KPK bitbase and the mating rule
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "8/8/8/3k4/8/8/8/Q3K3 b - - 0 1",
+        ] {
+            let mut board = Board::new();
+            position(&mut board, &format!("position fen {}", fen));
+            eval_print(&board);
+        }
+    }
+
+    #[test]
+    fn bench_counts_nodes() {
+        let nodes = bench(2);
+        assert!(nodes > 0);
+        // Fixed workload: the same count every run
+        assert_eq!(bench(2), nodes);
+    }
+
+    #[test]
+    fn info_line_reports_cp_and_mate_scores() {
+        let board = Board::new();
+        let pv = vec![uci_to_move(&board, "e2e4").unwrap(), Move::new(52, 36)];
+        let lines = [PvLine { score: 35, moves: pv.clone() }];
+        let info = SearchInfo {
+            depth: 7,
+            seldepth: 12,
+            nodes: 50_000,
+            elapsed: Duration::from_millis(500),
+            hashfull: 12,
+            lines: &lines,
+        };
+        assert_eq!(
+            info_line(&board, None, &info, &lines[0]),
+            "info depth 7 seldepth 12 score cp 35 nodes 50000 nps 100000 hashfull 12 time 500 pv e2e4 e7e5"
+        );
+
+        // Three plies to mate is mate in 2 for us; the losing side sees mate -2
+        let mating = 99_997;
+        assert_eq!(mate_in(mating), Some(2));
+        let line = PvLine { score: mating, moves: pv.clone() };
+        assert!(info_line(&board, Some(2), &info, &line).starts_with("info multipv 2 depth 7 seldepth 12 score mate 2 nodes"));
+        let line = PvLine { score: -mating, moves: pv };
+        assert!(info_line(&board, None, &info, &line).contains(" score mate -2 "));
+    }
+
+    #[test]
+    fn currmove_line_has_the_move_and_its_number() {
+        let board = Board::new();
+        let m = uci_to_move(&board, "g1f3").unwrap();
+        assert_eq!(currmove_line(&board, 9, m, 3), "info depth 9 currmove g1f3 currmovenumber 3");
+    }
+
+    #[test]
+    fn bestmove_line_with_ponder_move() {
+        let board = Board::new();
+        let best = uci_to_move(&board, "e2e4").unwrap();
+        let mut after = board.clone();
+        after.make_move(best);
+        let reply = uci_to_move(&after, "e7e5").unwrap();
+        let result = SearchResult { best_move: best, score: 20, depth: 2, seldepth: 2, nodes: 100, pv: vec![best, reply], mate: None };
+
+        let ponder = ponder_move(&SearchState::new(), &board, &result);
+        assert_eq!(ponder, Some(reply));
+        assert_eq!(bestmove_line(&board, best, ponder), "bestmove e2e4 ponder e7e5");
+        assert_eq!(bestmove_line(&board, best, None), "bestmove e2e4");
+    }
+
+    #[test]
+    fn mate_in_two_is_reported_as_mate() {
+        let mut board = Board::new();
+        position(&mut board, "position fen 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let mut state = SearchState::new();
+        let (sink, root) = (Arc::clone(&printed), board.clone());
+        state.set_info_callback(move |info| {
+            let line = info_line(&root, None, info, &info.lines[0]);
+            sink.lock().unwrap().push(line);
+        });
+        let limits = SearchLimits { depth: Some(5), ..Default::default() };
+        let result = state.search_ex(&mut board, &limits);
+
+        assert_eq!(move_to_uci(&board, result.best_move), "g3g6");
+        let printed = printed.lock().unwrap();
+        let last = printed.last().expect("an info line per iteration");
+        assert!(last.contains(" score mate 2 "), "{}", last);
+        assert!(!last.contains("score cp"));
+    }
+
+    #[test]
+    fn go_plays_a_polyglot_book_move() {
+        // One entry for the start position: e2e4 (from e2 = 12, to e4 = 28)
+        let board = Board::new();
+        let mut bytes = PolyglotKeys::default().key(&board).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&0x031cu16.to_be_bytes());
+        bytes.extend_from_slice(&[0, 1, 0, 0, 0, 0]);
+        let path = std::env::temp_dir().join(format!("uci-book-{}.bin", std::process::id()));
+        std::fs::write(&path, bytes).expect("write test book");
+
+        let mut options = EngineOptions::default();
+        let mut state = SearchState::new();
+        let mut set = |options: &mut EngineOptions, command: &str| {
+            let tokens: Vec<&str> = command.split_whitespace().collect();
+            set_option(options, &mut state, &tokens);
+        };
+        set(&mut options, &format!("setoption name BookFile value {}", path.display()));
+        let params = go("go wtime 60000 btime 60000");
+        assert!(book_move(&board, &params, &options).is_none(), "OwnBook is off by default");
+
+        set(&mut options, "setoption name OwnBook value true");
+        let m = book_move(&board, &params, &options).expect("start position is in the book");
+        assert_eq!(m.to_string(), "e2e4");
+        assert!(book_move(&board, &go("go infinite"), &options).is_none());
+        std::fs::remove_file(&path).expect("remove test book");
+    }
+
+    #[test]
+    fn position_keeps_only_the_reversible_history() {
+        // 1.e4 e5 then the knights go out and back twice: six reversible moves
+        let mut board = Board::new();
+        position(&mut board, "position startpos moves e2e4 e7e5 g1f3 b8c6 f3g1 c6b8 g1f3 b8c6");
+        assert_eq!(board.history_len(), 6);
+        assert_eq!(board.ply(), 8);
+        assert!(board.is_repetition());
+
+        // Everything back to the last pawn move can still be unmade
+        for _ in 0..6 {
+            board.unmake_move();
+        }
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        assert_eq!(board.history_len(), 0);
+
+        // A capture starts the window again
+        position(&mut board, "position startpos moves e2e4 d7d5 e4d5 d8d5 b1c3 d5a5 g1f3");
+        assert_eq!(board.history_len(), 3);
+        assert!(!board.is_repetition());
+    }
+}